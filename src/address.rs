@@ -0,0 +1,217 @@
+use crate::{Error, Result};
+use std::{fmt, str::FromStr};
+
+/// Absolute OSC address such as `/synth/osc.1/freq`.
+///
+/// Every segment must be non-empty and may not contain whitespace, control characters or any
+/// of the characters OSC reserves for patterns and separators: `#*,/?[]{}`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address(String);
+
+/// Whether `segment` can be one of the `/`-separated parts of an address, e.g. a node name.
+pub(crate) fn is_valid_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment.chars().all(|c| {
+            !c.is_whitespace()
+                && !c.is_control()
+                && !matches!(c, '#' | '*' | ',' | '/' | '?' | '[' | ']' | '{' | '}')
+        })
+}
+
+impl Address {
+    pub fn new(address: &str) -> Result<Address> {
+        let valid = match address.strip_prefix('/') {
+            Some("") => true,
+            Some(rest) => rest.split('/').all(is_valid_segment),
+            None => false,
+        };
+
+        if valid {
+            Ok(Address(address.to_owned()))
+        } else {
+            Err(Error::InvalidAddress(address.to_owned()))
+        }
+    }
+
+    pub fn root() -> Address {
+        Address("/".to_owned())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0 == "/"
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0[1..].split('/').filter(|s| !s.is_empty())
+    }
+
+    pub fn parent(&self) -> Option<Address> {
+        if self.is_root() {
+            return None;
+        }
+
+        match self.0.rfind('/') {
+            Some(0) => Some(Address::root()),
+            Some(i) => Some(Address(self.0[..i].to_owned())),
+            None => None,
+        }
+    }
+
+    /// Appends a relative path made of one or more `/`-separated segments.
+    pub fn join(&self, path: &str) -> Result<Address> {
+        if !path.split('/').all(is_valid_segment) {
+            return Err(Error::InvalidAddress(path.to_owned()));
+        }
+
+        if self.is_root() {
+            Ok(Address(format!("/{}", path)))
+        } else {
+            Ok(Address(format!("{}/{}", self.0, path)))
+        }
+    }
+
+    /// Whether `other` lies strictly below `self`.
+    pub fn is_ancestor_of(&self, other: &Address) -> bool {
+        if self.is_root() {
+            return !other.is_root();
+        }
+
+        other.0.len() > self.0.len()
+            && other.0.starts_with(&self.0)
+            && other.0.as_bytes()[self.0.len()] == b'/'
+    }
+
+    /// Appends a node name coming from libossia, which is trusted to be a valid segment.
+    pub(crate) fn child(&self, name: &str) -> Address {
+        if self.is_root() {
+            Address(format!("/{}", name))
+        } else {
+            Address(format!("{}/{}", self.0, name))
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Address> {
+        Address::new(s)
+    }
+}
+
+impl AsRef<Address> for Address {
+    fn as_ref(&self) -> &Address {
+        self
+    }
+}
+
+impl AsRef<str> for Address {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(s: &str) -> Address {
+        Address::new(s).unwrap()
+    }
+
+    #[test]
+    fn new_accepts_absolute_addresses() {
+        assert!(Address::new("/").is_ok());
+        assert!(Address::new("/synth").is_ok());
+        assert!(Address::new("/synth/osc.1/freq").is_ok());
+        assert!(Address::new("/gain-left_2").is_ok());
+    }
+
+    #[test]
+    fn new_rejects_invalid_addresses() {
+        for invalid in &[
+            "",
+            "synth",
+            "//",
+            "/synth/",
+            "/synth//freq",
+            "/a b",
+            "/a*",
+            "/a?",
+            "/a[1]",
+            "/{a,b}",
+            "/a#",
+            "/a,b",
+            "/a\n",
+        ] {
+            assert_eq!(
+                Address::new(invalid),
+                Err(Error::InvalidAddress((*invalid).to_owned())),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn from_str_and_display_round_trip() {
+        let parsed: Address = "/synth/freq".parse().unwrap();
+        assert_eq!(parsed.to_string(), "/synth/freq");
+        assert!("synth".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn parent() {
+        assert_eq!(Address::root().parent(), None);
+        assert_eq!(address("/synth").parent(), Some(Address::root()));
+        assert_eq!(
+            address("/synth/osc/freq").parent(),
+            Some(address("/synth/osc"))
+        );
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(Address::root().join("synth").unwrap(), address("/synth"));
+        assert_eq!(
+            address("/synth").join("osc/freq").unwrap(),
+            address("/synth/osc/freq")
+        );
+        assert!(address("/synth").join("").is_err());
+        assert!(address("/synth").join("/freq").is_err());
+        assert!(address("/synth").join("osc/").is_err());
+        assert!(address("/synth").join("f*").is_err());
+    }
+
+    #[test]
+    fn segments() {
+        assert_eq!(Address::root().segments().count(), 0);
+        assert_eq!(
+            address("/synth/osc.1/freq").segments().collect::<Vec<_>>(),
+            ["synth", "osc.1", "freq"]
+        );
+    }
+
+    #[test]
+    fn is_ancestor_of() {
+        let synth = address("/synth");
+        assert!(Address::root().is_ancestor_of(&synth));
+        assert!(!Address::root().is_ancestor_of(&Address::root()));
+        assert!(synth.is_ancestor_of(&address("/synth/freq")));
+        assert!(synth.is_ancestor_of(&address("/synth/osc/freq")));
+        assert!(!synth.is_ancestor_of(&synth));
+        assert!(!synth.is_ancestor_of(&address("/synthesizer")));
+        assert!(!synth.is_ancestor_of(&Address::root()));
+        assert!(!address("/synth/freq").is_ancestor_of(&synth));
+    }
+}
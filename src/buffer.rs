@@ -0,0 +1,149 @@
+use crate::lock::{lock, recover};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex, MutexGuard},
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+/// What a `BoundedQueue` or a `ValueStream` does with a new value while it is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    DropOldest,
+    DropNewest,
+    /// Blocks the libossia thread delivering the value until there is room for it, values must
+    /// not be pushed from the thread consuming them.
+    Block,
+    /// Replaces the pending value of the same parameter, or drops the new one if there is none.
+    CoalescePerParameter,
+}
+
+/// A buffer of fixed capacity between the libossia callbacks filling it and its consumer.
+pub(crate) struct Bounded<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+pub(crate) struct State<T> {
+    items: VecDeque<T>,
+    pub(crate) capacity: usize,
+    pub(crate) policy: OverflowPolicy,
+    pub(crate) dropped: u64,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Bounded<T> {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Bounded {
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                capacity: capacity.max(1),
+                policy,
+                dropped: 0,
+                closed: false,
+                waker: None,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, State<T>> {
+        lock(&self.state)
+    }
+
+    /// Buffers `item`, `coalesces` tells which pending items it replaces under
+    /// `CoalescePerParameter`.
+    pub(crate) fn push(&self, item: T, coalesces: impl Fn(&T) -> bool) {
+        let mut state = self.lock();
+        while state.items.len() >= state.capacity && !state.closed {
+            match state.policy {
+                OverflowPolicy::DropOldest => {
+                    state.items.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return;
+                }
+                OverflowPolicy::Block => state = recover(self.not_full.wait(state)),
+                OverflowPolicy::CoalescePerParameter => {
+                    state.dropped += 1;
+                    if let Some(pending) = state.items.iter_mut().rfind(|item| coalesces(item)) {
+                        *pending = item;
+                    }
+                    return;
+                }
+            }
+        }
+        if state.closed {
+            return;
+        }
+
+        state.items.push_back(item);
+        self.not_empty.notify_one();
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn pop(&self) -> Option<T> {
+        let item = self.lock().items.pop_front()?;
+        self.not_full.notify_one();
+        Some(item)
+    }
+
+    /// Waits up to `timeout` for an item.
+    pub(crate) fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                self.not_full.notify_one();
+                return Some(item);
+            }
+
+            let now = Instant::now();
+            if now >= deadline || state.closed {
+                return None;
+            }
+            state = recover(self.not_empty.wait_timeout(state, deadline - now)).0;
+        }
+    }
+
+    /// Pops the oldest item, or registers the task to wake up for the next one.
+    ///
+    /// Returns `Ready(None)` once the buffer is closed and empty.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_pop(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.lock();
+        match state.items.pop_front() {
+            Some(item) => {
+                self.not_full.notify_one();
+                Poll::Ready(Some(item))
+            }
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Refuses the items pushed from now on, those already buffered can still be popped.
+    ///
+    /// Also releases the callbacks blocked on a full buffer.
+    pub(crate) fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
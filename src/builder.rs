@@ -0,0 +1,219 @@
+use crate::{
+    address::is_valid_segment, string::c_string, Access, Bounding, Domain, Error, Node, Parameter,
+    Result, Value, ValueType,
+};
+use std::{ops::Range, os::raw::c_char};
+
+/// Types whose values can be checked against the min/max of a domain.
+pub trait Bounded {
+    fn within(&self, min: &Self, max: &Self) -> bool;
+}
+
+impl Bounded for i32 {
+    fn within(&self, min: &Self, max: &Self) -> bool {
+        min <= self && self <= max
+    }
+}
+
+impl Bounded for f32 {
+    fn within(&self, min: &Self, max: &Self) -> bool {
+        min <= self && self <= max
+    }
+}
+
+impl Bounded for c_char {
+    fn within(&self, min: &Self, max: &Self) -> bool {
+        min <= self && self <= max
+    }
+}
+
+impl Bounded for (f32, f32) {
+    fn within(&self, min: &Self, max: &Self) -> bool {
+        self.0.within(&min.0, &max.0) && self.1.within(&min.1, &max.1)
+    }
+}
+
+impl Bounded for (f32, f32, f32) {
+    fn within(&self, min: &Self, max: &Self) -> bool {
+        self.0.within(&min.0, &max.0)
+            && self.1.within(&min.1, &max.1)
+            && self.2.within(&min.2, &max.2)
+    }
+}
+
+impl Bounded for (f32, f32, f32, f32) {
+    fn within(&self, min: &Self, max: &Self) -> bool {
+        self.0.within(&min.0, &max.0)
+            && self.1.within(&min.1, &max.1)
+            && self.2.within(&min.2, &max.2)
+            && self.3.within(&min.3, &max.3)
+    }
+}
+
+type Within<T> = fn(&T, &T, &T) -> bool;
+
+/// Declares a child node and its parameter in one expression, nothing is created before [`build`].
+///
+/// [`build`]: ParameterBuilder::build
+pub struct ParameterBuilder<'a, T> {
+    node: &'a Node,
+    name: String,
+    range: Option<(Range<T>, Within<T>)>,
+    bounding: Option<Bounding>,
+    access: Option<Access>,
+    unit: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    default: Option<T>,
+    repetition_filter: Option<bool>,
+    critical: Option<bool>,
+}
+
+impl<'a, T: ValueType> ParameterBuilder<'a, T> {
+    pub(crate) fn new(node: &'a Node, name: &str) -> Self {
+        ParameterBuilder {
+            node,
+            name: name.to_owned(),
+            range: None,
+            bounding: None,
+            access: None,
+            unit: None,
+            description: None,
+            tags: Vec::new(),
+            default: None,
+            repetition_filter: None,
+            critical: None,
+        }
+    }
+
+    pub fn bounding(mut self, bounding: Bounding) -> Self {
+        self.bounding = Some(bounding);
+        self
+    }
+
+    pub fn access(mut self, access: Access) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    pub fn unit(mut self, unit: &str) -> Self {
+        self.unit = Some(unit.to_owned());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_owned());
+        self
+    }
+
+    pub fn default(mut self, value: T) -> Self {
+        self.default = Some(value);
+        self
+    }
+
+    pub fn repetition_filter(mut self, repetition_filter: bool) -> Self {
+        self.repetition_filter = Some(repetition_filter);
+        self
+    }
+
+    pub fn critical(mut self, critical: bool) -> Self {
+        self.critical = Some(critical);
+        self
+    }
+}
+
+impl<'a, T: ValueType + Bounded> ParameterBuilder<'a, T> {
+    /// Sets the min/max domain, the end of the range is inclusive as in libossia.
+    pub fn range(mut self, range: Range<T>) -> Self {
+        self.range = Some((range, T::within));
+        self
+    }
+}
+
+impl<'a, T> ParameterBuilder<'a, T>
+where
+    T: ValueType + Clone,
+    Value: From<T>,
+{
+    fn validate(&self) -> Result<()> {
+        if !is_valid_segment(&self.name) {
+            return Err(Error::InvalidName(self.name.clone()));
+        }
+        for s in self.unit.iter().chain(&self.description).chain(&self.tags) {
+            c_string(s)?;
+        }
+
+        if let Some((range, within)) = &self.range {
+            if !within(&range.start, &range.start, &range.end) {
+                return Err(Error::EmptyRange);
+            }
+
+            if let Some(default) = &self.default {
+                if !within(default, &range.start, &range.end) {
+                    return Err(Error::DefaultOutOfRange);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the declaration, then creates the node and its parameter.
+    ///
+    /// Fails with `ParameterExists` if the parent already has a child of that name.
+    pub fn build(self) -> Result<Parameter> {
+        self.validate()?;
+        // libossia would rename the new node `name.1` rather than fail
+        if self.node.find_child(&self.name).is_ok() {
+            return Err(Error::ParameterExists);
+        }
+
+        let mut node = self.node.add_child(&self.name)?;
+        // the node stays if libossia fails to create its parameter, removing it again could free
+        // it under the handle of an `on_node_created` callback
+        self.commit(&mut node)
+    }
+
+    fn commit(&self, node: &mut Node) -> Result<Parameter> {
+        let mut param = node.add_parameter(T::TYPE)?;
+
+        if let Some((range, _)) = &self.range {
+            let min = Value::from(range.start.clone());
+            let max = Value::from(range.end.clone());
+            param.set_domain(Domain::from(min..max));
+        }
+        if let Some(bounding) = self.bounding {
+            param.set_bounding_mode(bounding);
+        }
+        if let Some(access) = self.access {
+            param.set_access_mode(access);
+        }
+        if let Some(unit) = &self.unit {
+            param.set_unit(unit)?;
+        }
+        if let Some(repetition_filter) = self.repetition_filter {
+            param.set_repetition_filter(repetition_filter);
+        }
+        if let Some(critical) = self.critical {
+            param.set_critical(critical);
+        }
+        if let Some(description) = &self.description {
+            node.set_description(description)?;
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<&str> = self.tags.iter().map(String::as_str).collect();
+            node.set_tags(&tags)?;
+        }
+        if let Some(default) = &self.default {
+            node.set_default_value(Value::from(default.clone()));
+            param.set_value(Value::from(default.clone()));
+        }
+
+        Ok(param)
+    }
+}
@@ -1,23 +1,90 @@
 use crate::ffi;
-use crate::Node;
+use crate::string::{c_string, take_string};
+use crate::subscription;
 use crate::Protocol;
-use libffi::high::*;
-use std::{
-    ffi::{c_void, CStr},
-    os::raw::c_char,
+use crate::{
+    lock::{ReentrantGuard, ReentrantLock},
+    node::take_nodes,
+    weak::{Tracker, Tracking},
+    Node, Value, WeakNode, WeakParameter,
 };
+use crate::{Address, AddressPattern, Error, Parameter, Result, Subscription};
+use std::sync::{Arc, Weak};
 
-pub struct Device(pub(crate) ffi::ossia_device_t);
+/// A libossia device, clones share the same underlying device.
+///
+/// Every `Node`, `Parameter` and `Subscription` obtained from a device keeps it alive, it is
+/// freed once the last of them is dropped.
+///
+/// Devices, nodes, parameters and values can be sent to and shared with other threads. libossia
+/// locks the value of each parameter and the children of each node itself, and already calls into
+/// devices from the network threads of their protocols. Every other call through the handles of a
+/// device takes the device's tree lock. Removing nodes or parameters is `unsafe`, libossia frees
+/// them while other handles may still point to them, `WeakNode` and `WeakParameter` notice that.
+#[derive(Clone)]
+pub struct Device(Arc<DeviceHandle>);
 
-pub enum NodeCallbackId {
-    NodeCreated(ffi::ossia_node_callback_idx_t),
-    NodeRemoving(ffi::ossia_node_callback_idx_t),
-    ParameterDeleting(ffi::ossia_parameter_callback_idx_t),
+struct DeviceHandle {
+    device: ffi::ossia_device_t,
+    // serializes the node and parameter attributes, which libossia does not lock
+    tree: ReentrantLock,
+    // boxed so that its address, registered with libossia, stays put
+    tracker: Box<Tracker>,
+    tracking: Tracking,
 }
 
+// the thread safety of every libossia handle, as documented on `Device`
+unsafe impl Send for DeviceHandle {}
+unsafe impl Sync for DeviceHandle {}
+unsafe impl Send for Node {}
+unsafe impl Sync for Node {}
+unsafe impl Send for WeakNode {}
+unsafe impl Sync for WeakNode {}
+unsafe impl Send for Parameter {}
+unsafe impl Sync for Parameter {}
+unsafe impl Send for WeakParameter {}
+unsafe impl Sync for WeakParameter {}
+unsafe impl Send for Value {}
+unsafe impl Sync for Value {}
+
 impl Device {
-    pub fn new(protocol: Protocol, name: &str) -> Self {
-        Self(unsafe { ffi::ossia_device_create(protocol.0, name.as_ptr() as *const c_char) })
+    /// Creates a device named `name` exposed through `protocol`.
+    ///
+    /// The device takes ownership of the protocol and frees it together with itself.
+    pub fn new(protocol: Protocol, name: &str) -> Result<Self> {
+        let name = c_string(name)?;
+        let protocol = protocol.into_raw();
+        let device = unsafe { ffi::ossia_device_create(protocol, name.as_ptr()) };
+        if device.is_null() {
+            // libossia may already have taken the protocol over, leaking it is the only safe option
+            Err(Error::DeviceCreation)
+        } else {
+            let tracker = Box::new(Tracker::default());
+            let tracking = unsafe { tracker.start(device) };
+            Ok(Self(Arc::new(DeviceHandle {
+                device,
+                tree: ReentrantLock::default(),
+                tracker,
+                tracking,
+            })))
+        }
+    }
+
+    pub(crate) fn as_raw(&self) -> ffi::ossia_device_t {
+        self.0.device
+    }
+
+    /// Held across the libossia calls that read or change the tree, except for values.
+    pub(crate) fn lock_tree(&self) -> ReentrantGuard<'_> {
+        self.0.tree.lock()
+    }
+
+    pub(crate) fn tracker(&self) -> &Tracker {
+        &self.0.tracker
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakDevice {
+        WeakDevice(Arc::downgrade(&self.0))
     }
 
     pub fn reset() {
@@ -26,77 +93,121 @@ impl Device {
         }
     }
 
-    pub fn update_namespace(&mut self) {
-        // TODO what is the semantic of the return value?
-        let _err = unsafe { ffi::ossia_device_update_namespace(self.0) };
+    /// Fetches the namespace from the remote side of the protocol again.
+    ///
+    /// # Safety
+    ///
+    /// libossia replaces the nodes of the device, no `Node` or `Parameter` obtained from it before
+    /// may be used afterwards.
+    pub unsafe fn update_namespace(&mut self) -> Result<()> {
+        let _tree = self.lock_tree();
+        // returns whether the protocol managed to update the namespace
+        match ffi::ossia_device_update_namespace(self.as_raw()) {
+            0 => Err(Error::NamespaceUpdate),
+            _ => Ok(()),
+        }
     }
 
-    pub fn root(&self) -> Node {
-        Node(unsafe { ffi::ossia_device_get_root_node(self.0) })
+    pub fn root(&self) -> Result<Node> {
+        Node::from_raw(
+            unsafe { ffi::ossia_device_get_root_node(self.as_raw()) },
+            self,
+        )
     }
 
-    pub fn name(&self) -> &str {
-        unsafe { CStr::from_ptr(ffi::ossia_device_get_name(self.0)) }
-            .to_str()
-            .unwrap()
+    pub fn name(&self) -> Result<String> {
+        let _tree = self.lock_tree();
+        unsafe { take_string(ffi::ossia_device_get_name(self.as_raw())) }
     }
 
-    pub fn on_node_created<F>(&mut self, cb: F, ctx: *mut c_void) -> NodeCallbackId
-    where
-        F: Fn(*mut c_void, ffi::ossia_node_t),
-    {
-        let closure = Closure2::new(&cb);
+    /// Creates the node at `path` along with its missing parents.
+    pub fn create(&self, path: impl AsRef<Address>) -> Result<Node> {
+        let _tree = self.lock_tree();
+        let c_path = c_string(path.as_ref().as_str())?;
+        Node::from_raw(
+            unsafe { ffi::ossia_node_create(self.root_raw(), c_path.as_ptr()) },
+            self,
+        )
+    }
+
+    /// Looks up the node at `path`.
+    pub fn find(&self, path: impl AsRef<Address>) -> Result<Node> {
+        let _tree = self.lock_tree();
+        let path = path.as_ref();
+        let c_path = c_string(path.as_str())?;
+        let node = unsafe { ffi::ossia_node_find(self.root_raw(), c_path.as_ptr()) };
+        Node::from_raw(node, self).map_err(|_| Error::NotFound(path.to_string()))
+    }
+
+    /// Creates every node matching `pattern`, e.g. `/osc.{1,2}/freq`, along with their missing
+    /// parents.
+    pub fn create_pattern(&self, pattern: impl AsRef<AddressPattern>) -> Result<Vec<Node>> {
+        let _tree = self.lock_tree();
+        let pattern = c_string(pattern.as_ref().as_str())?;
+        let mut data: *mut ffi::ossia_node_t = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
 
-        NodeCallbackId::NodeCreated(unsafe {
-            ffi::ossia_device_add_node_created_callback(self.0, Some(*closure.code_ptr()), ctx)
-        })
+        unsafe {
+            ffi::ossia_node_create_pattern(self.root_raw(), pattern.as_ptr(), &mut data, &mut size);
+            Ok(take_nodes(data, size, self))
+        }
+    }
+
+    /// Looks up every node matching `pattern`.
+    pub fn find_pattern(&self, pattern: impl AsRef<AddressPattern>) -> Result<Vec<Node>> {
+        let _tree = self.lock_tree();
+        let pattern = c_string(pattern.as_ref().as_str())?;
+        let mut size: ffi::size_t = 0;
+        let mut ptr: *mut ffi::ossia_node_t = std::ptr::null_mut();
+
+        unsafe {
+            ffi::ossia_node_find_pattern(self.root_raw(), pattern.as_ptr(), &mut ptr, &mut size);
+            Ok(take_nodes(ptr, size, self))
+        }
+    }
+
+    // libossia resolves paths from the node it is given, whether or not they start with a `/`
+    fn root_raw(&self) -> ffi::ossia_node_t {
+        unsafe { ffi::ossia_device_get_root_node(self.as_raw()) }
     }
 
-    pub fn on_node_removing<F>(&mut self, cb: F, ctx: *mut c_void) -> NodeCallbackId
+    pub fn on_node_created<F>(&mut self, cb: F) -> Subscription
     where
-        F: Fn(*mut c_void, ffi::ossia_node_t),
+        F: FnMut(Node) + Send + 'static,
     {
-        let closure = Closure2::new(&cb);
+        subscription::on_node_created(self, cb)
+    }
 
-        NodeCallbackId::NodeRemoving(unsafe {
-            ffi::ossia_device_add_node_removing_callback(self.0, Some(*closure.code_ptr()), ctx)
-        })
+    pub fn on_node_removing<F>(&mut self, cb: F) -> Subscription
+    where
+        F: FnMut(Node) + Send + 'static,
+    {
+        subscription::on_node_removing(self, cb)
     }
 
-    pub fn on_parameter_deleting<F>(&mut self, cb: F, ctx: *mut c_void) -> NodeCallbackId
+    pub fn on_parameter_deleting<F>(&mut self, cb: F) -> Subscription
     where
-        F: Fn(*mut c_void, ffi::ossia_parameter_t),
+        F: FnMut(Parameter) + Send + 'static,
     {
-        let closure = Closure2::new(&cb);
-
-        NodeCallbackId::ParameterDeleting(unsafe {
-            ffi::ossia_device_add_parameter_deleting_callback(
-                self.0,
-                Some(*closure.code_ptr()),
-                ctx,
-            )
-        })
-    }
-
-    pub fn rm_callback(&mut self, id: NodeCallbackId) {
-        match id {
-            NodeCallbackId::NodeCreated(x) => unsafe {
-                ffi::ossia_device_remove_node_created_callback(self.0, x);
-            },
-            NodeCallbackId::NodeRemoving(x) => unsafe {
-                ffi::ossia_device_remove_node_removing_callback(self.0, x);
-            },
-            NodeCallbackId::ParameterDeleting(x) => unsafe {
-                ffi::ossia_device_remove_parameter_deleting_callback(self.0, x);
-            },
-        }
+        subscription::on_parameter_deleting(self, cb)
+    }
+}
+
+/// A `Device` that does not keep it alive.
+#[derive(Clone)]
+pub(crate) struct WeakDevice(Weak<DeviceHandle>);
+
+impl WeakDevice {
+    pub(crate) fn upgrade(&self) -> Option<Device> {
+        self.0.upgrade().map(Device)
     }
 }
 
-impl Drop for Device {
+impl Drop for DeviceHandle {
     fn drop(&mut self) {
         unsafe {
-            ffi::ossia_device_free(self.0);
+            self.tracker.stop(self.device, &self.tracking);
+            ffi::ossia_device_free(self.device);
         }
     }
 }
@@ -1,6 +1,6 @@
 use crate::ffi;
-use crate::Value;
-use std::{ops::Range, os::raw::c_char};
+use crate::{string::CStringArray, value::take_list, Error, Result, Value};
+use std::{convert::TryFrom, ops::Range, os::raw::c_char};
 
 pub struct Domain(pub(crate) ffi::ossia_domain_t);
 
@@ -18,11 +18,11 @@ impl Domain {
     }
 
     pub fn values(&self) -> Vec<Value> {
-        let values: *mut *mut ffi::ossia_value_t = std::ptr::null_mut();
+        let mut values: *mut ffi::ossia_value_t = std::ptr::null_mut();
         let mut n: ffi::size_t = 0;
         unsafe {
-            ffi::ossia_domain_get_values(self.0, values, &mut n);
-            Vec::from_raw_parts(values as *mut Value, n as usize, n as usize)
+            ffi::ossia_domain_get_values(self.0, &mut values, &mut n);
+            take_list(values, n)
         }
     }
 
@@ -57,14 +57,17 @@ impl From<Range<Value>> for Domain {
     }
 }
 
-impl From<&[&str]> for Domain {
-    fn from(set: &[&str]) -> Self {
-        Self(unsafe {
+impl TryFrom<&[&str]> for Domain {
+    type Error = Error;
+
+    fn try_from(set: &[&str]) -> Result<Self> {
+        let set = CStringArray::new(set)?;
+        Ok(Self(unsafe {
             ffi::ossia_domain_make_string_set(
                 set.as_ptr() as *mut *const c_char,
                 set.len() as ffi::size_t,
             )
-        })
+        }))
     }
 }
 
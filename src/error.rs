@@ -0,0 +1,98 @@
+use crate::ConversionError;
+use num_enum::{TryFromPrimitive, TryFromPrimitiveError};
+use std::{ffi::NulError, fmt, str::Utf8Error};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    NotFound(String),
+    InvalidName(String),
+    InvalidAddress(String),
+    InvalidPattern(String),
+    InvalidUtf8(Utf8Error),
+    InteriorNul(NulError),
+    UnknownEnumValue { name: &'static str, value: isize },
+    NullHandle,
+    ParameterExists,
+    EmptyRange,
+    DefaultOutOfRange,
+    InvalidConfig(&'static str),
+    ProtocolCreation,
+    DeviceCreation,
+    NamespaceUpdate,
+    Timeout,
+    Cancelled,
+    Conversion(ConversionError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "no node found at '{}'", path),
+            Error::InvalidName(name) => write!(f, "'{}' is not a valid node name", name),
+            Error::InvalidAddress(address) => write!(f, "'{}' is not a valid OSC address", address),
+            Error::InvalidPattern(pattern) => {
+                write!(f, "'{}' is not a valid OSC address pattern", pattern)
+            }
+            Error::InvalidUtf8(e) => {
+                write!(f, "string returned by libossia is not valid UTF-8: {}", e)
+            }
+            Error::InteriorNul(e) => {
+                write!(f, "string passed to libossia contains a NUL byte: {}", e)
+            }
+            Error::UnknownEnumValue { name, value } => {
+                write!(f, "unknown value {} for enum {}", value, name)
+            }
+            Error::NullHandle => write!(f, "libossia returned a null handle"),
+            Error::ParameterExists => write!(f, "node already has a parameter"),
+            Error::EmptyRange => write!(f, "range start is greater than its end"),
+            Error::DefaultOutOfRange => write!(f, "default value lies outside of the domain"),
+            Error::InvalidConfig(reason) => write!(f, "invalid protocol configuration: {}", reason),
+            Error::ProtocolCreation => write!(f, "failed to create protocol"),
+            Error::DeviceCreation => write!(f, "failed to create device"),
+            Error::NamespaceUpdate => write!(f, "failed to update device namespace"),
+            Error::Timeout => write!(f, "timed out waiting for libossia"),
+            Error::Cancelled => write!(f, "the runtime cancelled the request to libossia"),
+            Error::Conversion(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidUtf8(e) => Some(e),
+            Error::InteriorNul(e) => Some(e),
+            Error::Conversion(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(e: Utf8Error) -> Self {
+        Error::InvalidUtf8(e)
+    }
+}
+
+impl From<NulError> for Error {
+    fn from(e: NulError) -> Self {
+        Error::InteriorNul(e)
+    }
+}
+
+impl From<ConversionError> for Error {
+    fn from(e: ConversionError) -> Self {
+        Error::Conversion(e)
+    }
+}
+
+impl<T: TryFromPrimitive<Primitive = isize>> From<TryFromPrimitiveError<T>> for Error {
+    fn from(e: TryFromPrimitiveError<T>) -> Self {
+        Error::UnknownEnumValue {
+            name: T::NAME,
+            value: e.number,
+        }
+    }
+}
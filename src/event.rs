@@ -0,0 +1,60 @@
+use crate::{subscription, Address, Device, Result, Subscription};
+use crossbeam_channel::{Receiver, Sender};
+use std::ops::Deref;
+
+/// A change in the namespace of a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceEvent {
+    NodeCreated(Address),
+    NodeRemoving(Address),
+    ParameterDeleting(Address),
+}
+
+/// The receiving end of [`Device::events`], the device callbacks are unregistered when it is
+/// dropped.
+///
+/// It dereferences to a `crossbeam_channel::Receiver`, so it can be used in `select!`.
+pub struct DeviceEvents {
+    receiver: Receiver<DeviceEvent>,
+    _subscriptions: [Subscription; 3],
+}
+
+impl Deref for DeviceEvents {
+    type Target = Receiver<DeviceEvent>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+impl Device {
+    /// Forwards every namespace change of the device to a channel.
+    pub fn events(&self) -> DeviceEvents {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let (created, removing, deleting) = (sender.clone(), sender.clone(), sender);
+
+        DeviceEvents {
+            receiver,
+            _subscriptions: [
+                subscription::on_node_created(self, move |node| {
+                    send(&created, node.address().map(DeviceEvent::NodeCreated))
+                }),
+                subscription::on_node_removing(self, move |node| {
+                    send(&removing, node.address().map(DeviceEvent::NodeRemoving))
+                }),
+                subscription::on_parameter_deleting(self, move |param| {
+                    let address = param.node().and_then(|node| node.address());
+                    send(&deleting, address.map(DeviceEvent::ParameterDeleting))
+                }),
+            ],
+        }
+    }
+}
+
+fn send(sender: &Sender<DeviceEvent>, event: Result<DeviceEvent>) {
+    // nodes that cannot be located in the tree have no address to report, and sending only
+    // fails while the receiver is being dropped
+    if let Ok(event) = event {
+        let _ = sender.send(event);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{ffi, string::take_string, Address};
+use std::{collections::HashMap, ptr};
+
+/// Addresses of the nodes of a device, kept up to date by the `Tracker` of the device.
+///
+/// libossia does not expose the parent of a node, so each address would otherwise take a search
+/// of the whole tree. Nothing is recorded until an address is first asked for.
+#[derive(Default)]
+pub(crate) struct Index {
+    built: bool,
+    live: HashMap<ffi::ossia_node_t, Entry>,
+    // the subtree removed last, its nodes and parameters are still reported while libossia
+    // deletes them
+    departed: HashMap<ffi::ossia_node_t, Address>,
+    // nodes are mostly created next to the one located before
+    last: Option<ffi::ossia_node_t>,
+}
+
+struct Entry {
+    address: Address,
+    parent: ffi::ossia_node_t,
+}
+
+impl Index {
+    pub(crate) fn is_built(&self) -> bool {
+        self.built
+    }
+
+    /// The address of `node`, or of the removed node it was until another one is removed.
+    pub(crate) fn locate(
+        &mut self,
+        device: ffi::ossia_device_t,
+        node: ffi::ossia_node_t,
+    ) -> Option<Address> {
+        if !self.built {
+            self.rebuild(device);
+        }
+        if let Some(address) = self.departed.get(&node) {
+            return Some(address.clone());
+        }
+        if !self.live.contains_key(&node) && self.locate_near_last(node).is_none() {
+            self.rebuild(device);
+        }
+
+        let address = self.live.get(&node)?.address.clone();
+        self.last = Some(node);
+        Some(address)
+    }
+
+    /// `node` followed by its ancestors up to the root, with their addresses.
+    pub(crate) fn lineage(
+        &mut self,
+        device: ffi::ossia_device_t,
+        node: ffi::ossia_node_t,
+    ) -> Option<Vec<(Address, ffi::ossia_node_t)>> {
+        self.locate(device, node)?;
+
+        let mut lineage = Vec::new();
+        let mut current = node;
+        while let Some(entry) = self.live.get(&current) {
+            lineage.push((entry.address.clone(), current));
+            if entry.parent.is_null() {
+                return Some(lineage);
+            }
+            current = entry.parent;
+        }
+        // removed nodes have no ancestors left
+        None
+    }
+
+    pub(crate) fn created(&mut self, node: ffi::ossia_node_t) {
+        if !self.built {
+            return;
+        }
+
+        // the new node can reuse the memory of a removed one
+        self.departed.remove(&node);
+        self.locate_near_last(node);
+    }
+
+    /// Moves a removed subtree, as listed by `weak::subtree`, out of the live nodes.
+    pub(crate) fn removed(
+        &mut self,
+        device: ffi::ossia_device_t,
+        subtree: &[(ffi::ossia_node_t, ffi::ossia_node_t)],
+    ) {
+        let node = match subtree.first() {
+            Some(&(node, _)) => node,
+            None => return,
+        };
+        // libossia can also report the descendants of a removed node while deleting them
+        if !self.built || self.departed.contains_key(&node) {
+            return;
+        }
+
+        self.departed.clear();
+        let address = match self.locate(device, node) {
+            Some(address) => address,
+            None => return,
+        };
+        self.last = self
+            .live
+            .get(&node)
+            .map(|entry| entry.parent)
+            .filter(|parent| !parent.is_null());
+
+        self.live.remove(&node);
+        self.departed.insert(node, address);
+        for &(child, parent) in &subtree[1..] {
+            self.live.remove(&child);
+            let address = match (self.departed.get(&parent), name(child)) {
+                (Some(address), Some(name)) => address.child(&name),
+                _ => continue,
+            };
+            self.departed.insert(child, address);
+        }
+    }
+
+    /// Looks for the parent of `node` among the last node located and its ancestors.
+    fn locate_near_last(&mut self, node: ffi::ossia_node_t) -> Option<Address> {
+        let mut candidate = self.last;
+        while let Some(parent) = candidate {
+            let entry = self.live.get(&parent)?;
+            // libossia appends new children, the most recent are checked first
+            for i in (0..unsafe { ffi::ossia_node_child_size(parent) }).rev() {
+                if unsafe { ffi::ossia_node_get_child(parent, i) } == node {
+                    let address = entry.address.child(&name(node)?);
+                    self.live.insert(
+                        node,
+                        Entry {
+                            address: address.clone(),
+                            parent,
+                        },
+                    );
+                    self.last = Some(node);
+                    return Some(address);
+                }
+            }
+            candidate = Some(entry.parent).filter(|parent| !parent.is_null());
+        }
+        None
+    }
+
+    fn rebuild(&mut self, device: ffi::ossia_device_t) {
+        self.built = true;
+        self.live.clear();
+        self.last = None;
+
+        let root = unsafe { ffi::ossia_device_get_root_node(device) };
+        if root.is_null() {
+            return;
+        }
+
+        let mut pending = vec![(root, ptr::null_mut(), Address::root())];
+        while let Some((node, parent, address)) = pending.pop() {
+            for i in 0..unsafe { ffi::ossia_node_child_size(node) } {
+                let child = unsafe { ffi::ossia_node_get_child(node, i) };
+                // nodes being removed are no longer part of the tree
+                if child.is_null() || self.departed.contains_key(&child) {
+                    continue;
+                }
+                if let Some(name) = name(child) {
+                    pending.push((child, node, address.child(&name)));
+                }
+            }
+            self.live.insert(node, Entry { address, parent });
+        }
+    }
+}
+
+fn name(node: ffi::ossia_node_t) -> Option<String> {
+    unsafe { take_string(ffi::ossia_node_get_name(node)) }.ok()
+}
@@ -1,18 +1,45 @@
+mod address;
+mod buffer;
+mod builder;
 mod device;
 mod domain;
+mod error;
+mod event;
 mod ffi;
+mod index;
+mod lock;
 mod logger;
+mod mirror;
 mod mq;
 mod node;
 mod parameter;
+mod pattern;
 mod protocol;
+#[cfg(feature = "async")]
+mod stream;
+mod string;
+mod subscription;
 mod value;
+mod walk;
+mod weak;
 
+pub use address::*;
+pub use buffer::*;
+pub use builder::*;
 pub use device::*;
 pub use domain::*;
+pub use error::*;
+pub use event::*;
 pub use logger::*;
+pub use mirror::*;
 pub use mq::*;
 pub use node::*;
 pub use parameter::*;
+pub use pattern::*;
 pub use protocol::*;
+#[cfg(feature = "async")]
+pub use stream::*;
+pub use subscription::*;
 pub use value::*;
+pub use walk::*;
+pub use weak::*;
@@ -0,0 +1,64 @@
+use std::{
+    marker::PhantomData,
+    sync::{Condvar, LockResult, Mutex, MutexGuard, PoisonError},
+    thread::{self, ThreadId},
+};
+
+/// Locks `mutex` even if it was poisoned.
+///
+/// The crate only holds its locks across plain bookkeeping and user callbacks. A panicking
+/// callback is stopped at the libossia boundary and poisons the mutex of its own closure, whose
+/// state is then up to the closure, so there is nothing to refuse the next caller for.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    recover(mutex.lock())
+}
+
+/// Same as [`lock`] for the result of waiting on a condition variable.
+pub(crate) fn recover<T>(result: LockResult<T>) -> T {
+    result.unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A lock the thread holding it can take again.
+///
+/// libossia calls back synchronously while it changes a tree, and the callbacks must still be
+/// able to read the tree their thread is changing.
+#[derive(Default)]
+pub(crate) struct ReentrantLock {
+    // the thread holding the lock and how many times it took it
+    owner: Mutex<(Option<ThreadId>, usize)>,
+    released: Condvar,
+}
+
+/// Releases a `ReentrantLock` when dropped, on the thread that took it.
+pub(crate) struct ReentrantGuard<'a> {
+    lock: &'a ReentrantLock,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ReentrantLock {
+    pub(crate) fn lock(&self) -> ReentrantGuard<'_> {
+        let current = thread::current().id();
+        let mut owner = lock(&self.owner);
+        while matches!(owner.0, Some(thread) if thread != current) {
+            owner = recover(self.released.wait(owner));
+        }
+        owner.0 = Some(current);
+        owner.1 += 1;
+
+        ReentrantGuard {
+            lock: self,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for ReentrantGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = lock(&self.lock.owner);
+        owner.1 -= 1;
+        if owner.1 == 0 {
+            owner.0 = None;
+            self.lock.released.notify_one();
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use std::os::raw::c_char;
-
-use crate::ffi::{self, log_level};
+use crate::{
+    ffi::{self, log_level},
+    string::c_string,
+    Error, Result,
+};
 use enum_repr::EnumRepr;
 
 #[EnumRepr(type = "log_level")]
@@ -17,25 +19,31 @@ pub enum LogLevel {
 pub struct Logger(ffi::ossia_logger_t);
 
 impl Logger {
-    pub fn new(host: &str, app: &str) -> Logger {
-        Self(unsafe {
-            ffi::ossia_logger_create(
-                host.as_ptr() as *const c_char,
-                app.as_ptr() as *const c_char,
-            )
-        })
+    pub fn new(host: &str, app: &str) -> Result<Logger> {
+        let host = c_string(host)?;
+        let app = c_string(app)?;
+        let logger = unsafe { ffi::ossia_logger_create(host.as_ptr(), app.as_ptr()) };
+        if logger.is_null() {
+            Err(Error::NullHandle)
+        } else {
+            Ok(Self(logger))
+        }
     }
 
-    pub fn heatbeat(&self, pid: i32, cmdline: &str) {
-        unsafe { ffi::ossia_logger_init_heartbeat(self.0, pid, cmdline.as_ptr() as *const c_char) };
+    pub fn heatbeat(&self, pid: i32, cmdline: &str) -> Result<()> {
+        let cmdline = c_string(cmdline)?;
+        unsafe { ffi::ossia_logger_init_heartbeat(self.0, pid, cmdline.as_ptr()) };
+        Ok(())
     }
 
     pub fn level(&mut self, lvl: LogLevel) {
         unsafe { ffi::ossia_logger_set_level(self.0, lvl as log_level) };
     }
 
-    pub fn log(&mut self, lvl: LogLevel, message: &str) {
-        unsafe { ffi::ossia_log(self.0, lvl as log_level, message.as_ptr() as *const c_char) };
+    pub fn log(&mut self, lvl: LogLevel, message: &str) -> Result<()> {
+        let message = c_string(message)?;
+        unsafe { ffi::ossia_log(self.0, lvl as log_level, message.as_ptr()) };
+        Ok(())
     }
 }
 
@@ -0,0 +1,239 @@
+use crate::{lock::lock, Device, OscQueryMirrorConfig, Result};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use std::{
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Whether a mirrored OSCQuery server can currently be reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected { reason: String },
+}
+
+/// Shortest delay a `ReconnectPolicy` accepts, a zero delay would retry or probe in a busy loop.
+const MIN_DELAY: Duration = Duration::from_millis(1);
+
+/// How a `MirrorConnection` watches its server and retries once it is gone.
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    factor: u32,
+    probe_interval: Duration,
+}
+
+impl ReconnectPolicy {
+    pub fn new() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+            factor: 2,
+            probe_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Delay before the first retry, 250 ms by default and at least 1 ms.
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay.max(MIN_DELAY);
+        self
+    }
+
+    /// Upper bound of the delay between retries, 10 s by default.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// What the delay is multiplied by after each failed retry, 2 by default.
+    pub fn factor(mut self, factor: u32) -> Self {
+        self.factor = factor.max(1);
+        self
+    }
+
+    /// How often the server is checked while connected, every second by default and at most
+    /// every millisecond.
+    ///
+    /// It also bounds how long a single check may take.
+    pub fn probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = interval.max(MIN_DELAY);
+        self
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A device mirroring an OSCQuery server, with a thread following the connection to it.
+///
+/// ossia-c does not report the state of the mirror, so the thread probes the WebSocket port of
+/// the server. Once the server can be reached again after going away, the thread creates a new
+/// mirror device and fetches its namespace before reporting `Connected`. A server restarting
+/// faster than the probe interval goes unnoticed.
+pub struct MirrorConnection {
+    device: Arc<Mutex<Device>>,
+    state: Arc<Mutex<ConnectionState>>,
+    events: Receiver<ConnectionState>,
+    // dropping the sender stops the thread
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MirrorConnection {
+    /// Creates the mirror device named `name` and fetches its namespace, the server must be
+    /// reachable at that point.
+    pub fn connect(
+        config: &OscQueryMirrorConfig,
+        name: &str,
+        policy: ReconnectPolicy,
+    ) -> Result<MirrorConnection> {
+        let device = Arc::new(Mutex::new(mirror(config, name)?));
+        let state = Arc::new(Mutex::new(ConnectionState::Connected));
+        let (sender, events) = crossbeam_channel::unbounded();
+        let (stop, stopped) = crossbeam_channel::bounded(0);
+
+        let supervisor = Supervisor {
+            config: OscQueryMirrorConfig::new(config.server()),
+            name: name.to_owned(),
+            policy,
+            device: device.clone(),
+            state: state.clone(),
+            events: sender,
+            stopped,
+        };
+        let thread = thread::spawn(move || supervisor.run());
+
+        Ok(MirrorConnection {
+            device,
+            state,
+            events,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// The current mirror device.
+    ///
+    /// Each reconnection replaces it, the handles obtained from an earlier device keep referring
+    /// to that device, which no longer follows the server.
+    pub fn device(&self) -> Device {
+        lock(&self.device).clone()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        lock(&self.state).clone()
+    }
+
+    /// Every change of state after the first connection.
+    ///
+    /// `Connected` is sent once the new device has fetched the namespace of the server.
+    pub fn events(&self) -> &Receiver<ConnectionState> {
+        &self.events
+    }
+}
+
+impl Drop for MirrorConnection {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Creates a mirror device and fetches the namespace of the server into it.
+fn mirror(config: &OscQueryMirrorConfig, name: &str) -> Result<Device> {
+    let mut device = Device::new(config.build()?, name)?;
+    // the device is new, there are no nodes nor parameters obtained from it yet
+    unsafe { device.update_namespace()? };
+    Ok(device)
+}
+
+struct Supervisor {
+    config: OscQueryMirrorConfig,
+    name: String,
+    policy: ReconnectPolicy,
+    device: Arc<Mutex<Device>>,
+    state: Arc<Mutex<ConnectionState>>,
+    events: Sender<ConnectionState>,
+    stopped: Receiver<()>,
+}
+
+impl Supervisor {
+    fn run(self) {
+        while self.watch() && self.reconnect() {}
+    }
+
+    /// Probes the server until it goes away, returns `false` once the connection is dropped.
+    fn watch(&self) -> bool {
+        loop {
+            if !self.sleep(self.policy.probe_interval) {
+                return false;
+            }
+            if let Err(reason) = self.probe() {
+                self.set(ConnectionState::Disconnected { reason });
+                return true;
+            }
+        }
+    }
+
+    /// Retries with backoff until a new device mirrors the server, returns `false` once the
+    /// connection is dropped.
+    fn reconnect(&self) -> bool {
+        let mut delay = self.policy.initial_delay;
+        loop {
+            if !self.sleep(delay) {
+                return false;
+            }
+            delay = delay
+                .saturating_mul(self.policy.factor)
+                .min(self.policy.max_delay);
+
+            if self.probe().is_err() {
+                continue;
+            }
+            self.set(ConnectionState::Connecting);
+            match mirror(&self.config, &self.name) {
+                Ok(device) => {
+                    *lock(&self.device) = device;
+                    self.set(ConnectionState::Connected);
+                    return true;
+                }
+                Err(e) => self.set(ConnectionState::Disconnected {
+                    reason: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    fn probe(&self) -> Result<(), String> {
+        TcpStream::connect_timeout(&self.config.server(), self.policy.probe_interval)
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Waits for `duration`, returns `false` if the connection was dropped meanwhile.
+    fn sleep(&self, duration: Duration) -> bool {
+        matches!(
+            self.stopped.recv_timeout(duration),
+            Err(RecvTimeoutError::Timeout)
+        )
+    }
+
+    /// Records `state` and sends it, unless the connection already was in that state.
+    fn set(&self, state: ConnectionState) {
+        let mut current = lock(&self.state);
+        if *current == state {
+            return;
+        }
+        *current = state.clone();
+        let _ = self.events.send(state);
+    }
+}
@@ -1,30 +1,89 @@
-use crate::{ffi, Device, Parameter, Value};
+use crate::{
+    buffer::Bounded, ffi, subscription, Device, OverflowPolicy, OwnedValue, Parameter,
+    Subscription, Value,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
-pub struct MessageQueue(pub(crate) ffi::ossia_mq_t);
+/// How often `pop_timeout` polls, libossia queues cannot be waited on.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Operations shared by the message queues, on top of a non-blocking `pop`.
+pub trait Queue {
+    fn pop(&mut self) -> Option<(Parameter, Value)>;
+
+    /// Waits up to `timeout` for a message.
+    fn pop_timeout(&mut self, timeout: Duration) -> Option<(Parameter, Value)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(message) = self.pop() {
+                return Some(message);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Pops every message currently queued.
+    fn drain(&mut self) -> Drain<'_, Self>
+    where
+        Self: Sized,
+    {
+        Drain(self)
+    }
+}
+
+/// Iterator returned by [`Queue::drain`], values of unknown type are skipped.
+pub struct Drain<'a, Q>(&'a mut Q);
+
+impl<'a, Q: Queue> Iterator for Drain<'a, Q> {
+    type Item = (Parameter, OwnedValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (param, value) = self.0.pop()?;
+            if let Ok(value) = OwnedValue::try_from(value) {
+                return Some((param, value));
+            }
+        }
+    }
+}
+
+/// Queues the values received by the registered parameters of a device.
+pub struct MessageQueue(pub(crate) ffi::ossia_mq_t, Device);
 
 impl MessageQueue {
-    pub fn new(device: Device) -> MessageQueue {
-        MessageQueue(unsafe { ffi::ossia_mq_create(device.0) })
+    pub fn new(device: &Device) -> MessageQueue {
+        MessageQueue(
+            unsafe { ffi::ossia_mq_create(device.as_raw()) },
+            device.clone(),
+        )
     }
 
-    pub fn register(&mut self, param: Parameter) {
+    pub fn register(&mut self, param: &Parameter) {
         unsafe { ffi::ossia_mq_register(self.0, param.0) }
     }
 
-    pub fn unregister(&mut self, param: Parameter) {
+    pub fn unregister(&mut self, param: &Parameter) {
         unsafe { ffi::ossia_mq_unregister(self.0, param.0) }
     }
+}
 
-    pub fn pop(&mut self) -> Option<(Parameter, Value)> {
-        let param: *mut ffi::ossia_parameter_t = std::ptr::null_mut();
-        let value: *mut ffi::ossia_value_t = std::ptr::null_mut();
-        let r = unsafe { ffi::ossia_mq_pop(self.0, param, value) };
-
-        if r == 1 {
-            Some((Parameter(unsafe { *param }), Value(unsafe { *value })))
-        } else {
-            None
-        }
+impl Queue for MessageQueue {
+    fn pop(&mut self) -> Option<(Parameter, Value)> {
+        let mut param: ffi::ossia_parameter_t = std::ptr::null_mut();
+        let mut value: ffi::ossia_value_t = std::ptr::null_mut();
+        let r = unsafe { ffi::ossia_mq_pop(self.0, &mut param, &mut value) };
+        take_message(r, param, value, &self.1)
     }
 }
 
@@ -33,3 +92,173 @@ impl Drop for MessageQueue {
         unsafe { ffi::ossia_mq_free(self.0) }
     }
 }
+
+/// Queues the values received by every parameter of a device.
+///
+/// libossia listens to the messages of the whole device, parameters created later are included
+/// without registering them.
+pub struct GlobalMessageQueue(ffi::ossia_global_mq_t, Device);
+
+impl GlobalMessageQueue {
+    pub fn new(device: &Device) -> GlobalMessageQueue {
+        GlobalMessageQueue(
+            unsafe { ffi::ossia_global_mq_create(device.as_raw()) },
+            device.clone(),
+        )
+    }
+}
+
+impl Queue for GlobalMessageQueue {
+    fn pop(&mut self) -> Option<(Parameter, Value)> {
+        let mut param: ffi::ossia_parameter_t = std::ptr::null_mut();
+        let mut value: ffi::ossia_value_t = std::ptr::null_mut();
+        let r = unsafe { ffi::ossia_global_mq_pop(self.0, &mut param, &mut value) };
+        take_message(r, param, value, &self.1)
+    }
+}
+
+impl Drop for GlobalMessageQueue {
+    fn drop(&mut self) {
+        unsafe { ffi::ossia_global_mq_free(self.0) }
+    }
+}
+
+/// Wraps another queue and keeps only the latest pending value of each parameter.
+///
+/// Parameters are popped in the order their first pending value arrived.
+pub struct CoalescingQueue<Q = MessageQueue> {
+    queue: Q,
+    pending: VecDeque<(Parameter, Value)>,
+    // position in `pending` of each parameter, counted from the first message ever popped
+    index: HashMap<ffi::ossia_parameter_t, usize>,
+    popped: usize,
+    merged: usize,
+}
+
+impl<Q: Queue> CoalescingQueue<Q> {
+    pub fn new(queue: Q) -> Self {
+        CoalescingQueue {
+            queue,
+            pending: VecDeque::new(),
+            index: HashMap::new(),
+            popped: 0,
+            merged: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Q {
+        &self.queue
+    }
+
+    /// Gives access to the wrapped queue, e.g. to register more parameters.
+    pub fn get_mut(&mut self) -> &mut Q {
+        &mut self.queue
+    }
+
+    /// Number of values that were replaced by a newer one before being popped.
+    pub fn merged(&self) -> usize {
+        self.merged
+    }
+
+    fn collect(&mut self) {
+        while let Some((param, value)) = self.queue.pop() {
+            match self.index.get(&param.0) {
+                Some(&position) => {
+                    self.pending[position - self.popped].1 = value;
+                    self.merged += 1;
+                }
+                None => {
+                    self.index.insert(param.0, self.popped + self.pending.len());
+                    self.pending.push_back((param, value));
+                }
+            }
+        }
+    }
+}
+
+impl<Q: Queue> Queue for CoalescingQueue<Q> {
+    fn pop(&mut self) -> Option<(Parameter, Value)> {
+        self.collect();
+        let (param, value) = self.pending.pop_front()?;
+        self.index.remove(&param.0);
+        self.popped += 1;
+        Some((param, value))
+    }
+}
+
+/// Queues the values received by the registered parameters in a buffer of fixed capacity.
+///
+/// Unlike `MessageQueue` it is fed straight from the parameter callbacks, so a consumer that
+/// stops popping cannot make it grow.
+pub struct BoundedQueue {
+    buffer: Arc<Bounded<(Parameter, Value)>>,
+    subscriptions: HashMap<ffi::ossia_parameter_t, Subscription>,
+}
+
+impl BoundedQueue {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> BoundedQueue {
+        BoundedQueue {
+            buffer: Arc::new(Bounded::new(capacity, policy)),
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, param: &Parameter) {
+        let buffer = self.buffer.clone();
+        let source = param.clone();
+        self.subscriptions.entry(param.0).or_insert_with(|| {
+            subscription::on_value(param, move |value| {
+                buffer.push((source.clone(), value), |(pending, _)| {
+                    pending.0 == source.0
+                })
+            })
+        });
+    }
+
+    /// Stops queueing the values of `param`, those already queued can still be popped.
+    pub fn unregister(&mut self, param: &Parameter) {
+        self.subscriptions.remove(&param.0);
+    }
+
+    /// Number of values discarded because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.lock().dropped
+    }
+}
+
+impl Queue for BoundedQueue {
+    fn pop(&mut self) -> Option<(Parameter, Value)> {
+        self.buffer.pop()
+    }
+
+    fn pop_timeout(&mut self, timeout: Duration) -> Option<(Parameter, Value)> {
+        self.buffer.pop_timeout(timeout)
+    }
+}
+
+impl Drop for BoundedQueue {
+    fn drop(&mut self) {
+        // releases the callbacks blocked on a full queue before they get unregistered
+        self.buffer.close();
+    }
+}
+
+/// Wraps the out-parameters of `ossia_mq_pop` and `ossia_global_mq_pop`, the value is handed
+/// over to the caller.
+fn take_message(
+    r: std::os::raw::c_int,
+    param: ffi::ossia_parameter_t,
+    value: ffi::ossia_value_t,
+    device: &Device,
+) -> Option<(Parameter, Value)> {
+    if r == 0 || value.is_null() {
+        return None;
+    }
+
+    let value = Value(value);
+    if param.is_null() {
+        None
+    } else {
+        Some((Parameter(param, device.clone()), value))
+    }
+}
@@ -1,191 +1,375 @@
-use crate::{ffi, Device, Parameter, Type, Value};
+use crate::{
+    ffi::{self, ossia_type},
+    string::{c_string, take_optional_string, take_string, CStringArray},
+    subscription, Address, Ancestors, Device, Error, Parameter, ParameterBuilder, Result,
+    Subscription, Type, Value, ValueType, Walk,
+};
 use std::{
-    ffi::{c_void, CStr},
+    ffi::CStr,
     ops::Range,
-    os::raw::c_char,
+    os::raw::{c_char, c_int},
 };
 
-pub struct Node(pub(crate) ffi::ossia_node_t);
-
-pub struct NodeCallbackId(ffi::ossia_node_callback_idx_t);
+/// A node of a device tree, it keeps its device alive.
+#[derive(Clone)]
+pub struct Node(pub(crate) ffi::ossia_node_t, pub(crate) Device);
 
 impl Node {
-    pub fn new(&self, path: &str) -> Self {
-        Node(unsafe { ffi::ossia_node_create(self.0, path.as_ptr() as *const c_char) })
+    pub(crate) fn from_raw(node: ffi::ossia_node_t, device: &Device) -> Result<Node> {
+        if node.is_null() {
+            Err(Error::NullHandle)
+        } else {
+            Ok(Node(node, device.clone()))
+        }
     }
 
-    pub fn from_pattern(&self, pattern: &str) -> Vec<Node> {
-        let mut data = std::ptr::null_mut();
-        let mut size = 0;
-        unsafe {
-            ffi::ossia_node_create_pattern(
-                self.0,
-                pattern.as_ptr() as *const c_char,
-                &mut data,
-                &mut size,
-            );
-        }
-        todo!()
+    pub fn add_child(&self, name: &str) -> Result<Node> {
+        let _tree = self.1.lock_tree();
+        let name = c_string(name)?;
+        Node::from_raw(
+            unsafe { ffi::ossia_node_add_child(self.0, name.as_ptr()) },
+            &self.1,
+        )
     }
 
-    pub fn find(&self, path: &str) -> Self {
-        Node(unsafe { ffi::ossia_node_find(self.0, path.as_ptr() as *const c_char) })
+    /// Removes `child` and everything below it.
+    ///
+    /// # Safety
+    ///
+    /// libossia frees the removed nodes and their parameters right away, no handle to any of them
+    /// may be used afterwards.
+    pub unsafe fn rm_child(&self, child: Node) {
+        let _tree = self.1.lock_tree();
+        ffi::ossia_node_remove_child(self.0, child.0)
     }
 
-    pub fn find_pattern(&self, pattern: &str) -> Vec<Node> {
-        let mut size: ffi::size_t = 0;
-        let mut ptr: *mut ffi::ossia_node_t = std::ptr::null_mut();
+    pub fn name(&self) -> Result<String> {
+        let _tree = self.1.lock_tree();
+        unsafe { take_string(ffi::ossia_node_get_name(self.0)) }
+    }
 
-        unsafe {
-            ffi::ossia_node_find_pattern(
-                self.0,
-                pattern.as_ptr() as *const c_char,
-                &mut ptr,
-                &mut size,
-            );
-            Vec::from_raw_parts(ptr as *mut Node, size as usize, size as usize)
-        }
+    pub fn device(&self) -> Device {
+        self.1.clone()
     }
 
-    pub fn add_child(&self, name: &str) -> Node {
-        Node(unsafe { ffi::ossia_node_add_child(self.0, name.as_ptr() as *const c_char) })
+    pub fn descendants(&self) -> Result<Walk> {
+        Ok(Walk::depth_first(self.address()?, self))
     }
 
-    pub fn rm_child(&self, name: Node) {
-        unsafe { ffi::ossia_node_remove_child(self.0, name.0) }
+    pub fn walk_bfs(&self) -> Result<Walk> {
+        Ok(Walk::breadth_first(self.address()?, self))
     }
 
-    pub fn name(&self) -> &str {
-        unsafe { CStr::from_ptr(ffi::ossia_node_get_name(self.0)) }
-            .to_str()
-            .unwrap()
+    pub fn ancestors(&self) -> Result<Ancestors> {
+        let mut lineage = self.lineage()?;
+        // the node itself is not one of its ancestors
+        lineage.remove(0);
+
+        let ancestors = lineage
+            .into_iter()
+            .map(|(address, node)| (address, Node(node, self.1.clone())))
+            .collect();
+        Ok(Ancestors::new(ancestors))
     }
 
-    pub fn device(&self) -> Device {
-        Device(unsafe { ffi::ossia_node_get_device(self.0) })
+    pub fn address(&self) -> Result<Address> {
+        let _tree = self.1.lock_tree();
+        match self.1.tracker().address(self.1.as_raw(), self.0) {
+            Some(address) => Ok(address),
+            None => Err(Error::NotFound(self.name()?)),
+        }
+    }
+
+    /// `self` and its ancestors up to the device root, libossia does not expose the parent of a
+    /// node so the device keeps track of them.
+    fn lineage(&self) -> Result<Vec<(Address, ffi::ossia_node_t)>> {
+        let _tree = self.1.lock_tree();
+        match self.1.tracker().lineage(self.1.as_raw(), self.0) {
+            Some(lineage) => Ok(lineage),
+            None => Err(Error::NotFound(self.name()?)),
+        }
     }
 
     pub fn num_children(&self) -> i32 {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_node_child_size(self.0) }
     }
 
-    pub fn child(&self, idx: i32) -> Node {
-        Node(unsafe { ffi::ossia_node_get_child(self.0, idx) })
+    pub fn child(&self, idx: i32) -> Result<Node> {
+        let _tree = self.1.lock_tree();
+        Node::from_raw(unsafe { ffi::ossia_node_get_child(self.0, idx) }, &self.1)
+    }
+
+    pub fn find_child(&self, name: &str) -> Result<Node> {
+        let _tree = self.1.lock_tree();
+        let c_name = c_string(name)?;
+        let node = unsafe { ffi::ossia_node_find_child(self.0, c_name.as_ptr()) };
+        Node::from_raw(node, &self.1).map_err(|_| Error::NotFound(name.to_owned()))
+    }
+
+    pub fn add_parameter(&self, typ: Type) -> Result<Parameter> {
+        let _tree = self.1.lock_tree();
+        if self.parameter().is_some() {
+            return Err(Error::ParameterExists);
+        }
+
+        let param = unsafe { ffi::ossia_node_create_parameter(self.0, typ as ossia_type) };
+        if param.is_null() {
+            Err(Error::NullHandle)
+        } else {
+            Ok(Parameter(param, self.1.clone()))
+        }
+    }
+
+    pub fn create_parameter<T: ValueType>(&self) -> Result<Parameter> {
+        self.add_parameter(T::TYPE)
     }
 
-    pub fn find_child(&self, name: &str) -> Node {
-        Node(unsafe { ffi::ossia_node_find_child(self.0, name.as_ptr() as *const c_char) })
+    pub fn parameter_builder<T: ValueType>(&self, name: &str) -> ParameterBuilder<'_, T> {
+        ParameterBuilder::new(self, name)
     }
 
-    pub fn add_parameter(&self, typ: Type) {
-        // ossia_node_create_parameter or ossia_create_parameter
-        todo!()
+    pub fn parameter(&self) -> Option<Parameter> {
+        let _tree = self.1.lock_tree();
+        let param = unsafe { ffi::ossia_node_get_parameter(self.0) };
+        if param.is_null() {
+            None
+        } else {
+            Some(Parameter(param, self.1.clone()))
+        }
     }
 
-    pub fn parameter(&self) -> Parameter {
-        Parameter(unsafe { ffi::ossia_node_get_parameter(self.0) })
+    /// Deletes the parameter of this node.
+    ///
+    /// # Safety
+    ///
+    /// libossia frees the parameter right away, no handle to it may be used afterwards.
+    pub unsafe fn rm_parameter(&self) {
+        let _tree = self.1.lock_tree();
+        ffi::ossia_node_remove_parameter(self.0)
     }
 
-    pub fn rm_parameter(&self) {
-        unsafe { ffi::ossia_node_remove_parameter(self.0) }
+    /// Calls `cb` when this node is about to be deleted.
+    pub fn add_callback<F>(&mut self, cb: F) -> Subscription
+    where
+        F: FnMut(Node) + Send + 'static,
+    {
+        subscription::on_node_deleting(self, cb)
     }
 
-    pub fn add_callback<F>(&mut self, cb: F, ctx: *mut c_void) -> NodeCallbackId
+    /// Calls `cb` for every node created anywhere below this one.
+    pub fn on_descendant_created<F>(&self, cb: F) -> Result<Subscription>
     where
-        F: Fn(*mut c_void, Node),
+        F: FnMut(Address, Node) + Send + 'static,
     {
-        todo!()
+        Ok(subscription::on_node_created(
+            &self.1,
+            below(self.address()?, cb),
+        ))
     }
 
-    pub fn description(&self) -> &str {
-        unsafe { CStr::from_ptr(ffi::ossia_node_get_description(self.0)) }
-            .to_str()
-            .unwrap()
+    /// Calls `cb` for every node about to be removed anywhere below this one.
+    pub fn on_descendant_removing<F>(&self, cb: F) -> Result<Subscription>
+    where
+        F: FnMut(Address, Node) + Send + 'static,
+    {
+        Ok(subscription::on_node_removing(
+            &self.1,
+            below(self.address()?, cb),
+        ))
     }
 
-    pub fn set_description(&mut self, desc: &str) {
-        unsafe { ffi::ossia_node_set_description(self.0, desc.as_ptr() as *const c_char) }
+    pub fn description(&self) -> Result<Option<String>> {
+        let _tree = self.1.lock_tree();
+        unsafe { take_optional_string(ffi::ossia_node_get_description(self.0)) }
     }
 
-    pub fn extended_type(&self) -> &str {
-        todo!()
+    pub fn set_description(&mut self, desc: &str) -> Result<()> {
+        let _tree = self.1.lock_tree();
+        let desc = c_string(desc)?;
+        unsafe { ffi::ossia_node_set_description(self.0, desc.as_ptr()) };
+        Ok(())
     }
 
-    pub fn set_extended_type(&mut self, ext_type: &str) {
-        todo!()
+    pub fn extended_type(&self) -> Result<Option<String>> {
+        let _tree = self.1.lock_tree();
+        unsafe { take_optional_string(ffi::ossia_node_get_extended_type(self.0)) }
     }
 
-    pub fn tags(&self) -> Vec<&str> {
-        todo!()
+    pub fn set_extended_type(&mut self, ext_type: &str) -> Result<()> {
+        let _tree = self.1.lock_tree();
+        let ext_type = c_string(ext_type)?;
+        unsafe { ffi::ossia_node_set_extended_type(self.0, ext_type.as_ptr()) };
+        Ok(())
     }
 
-    pub fn set_tags(&mut self, tags: &[&str]) {
-        todo!()
+    pub fn tags(&self) -> Result<Option<Vec<String>>> {
+        let _tree = self.1.lock_tree();
+        let mut tags: *mut *mut c_char = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_node_get_tags(self.0, &mut tags, &mut size) };
+
+        if tags.is_null() {
+            return Ok(None);
+        }
+
+        let copied = unsafe { std::slice::from_raw_parts(tags, size as usize) }
+            .iter()
+            .map(|&tag| unsafe { CStr::from_ptr(tag) }.to_str().map(str::to_owned))
+            .collect::<Result<Vec<_>, _>>();
+        unsafe { ffi::ossia_tags_free(tags, size) };
+        Ok(Some(copied?))
     }
 
-    // pub fn tags_free
+    pub fn set_tags(&mut self, tags: &[&str]) -> Result<()> {
+        let _tree = self.1.lock_tree();
+        let tags = CStringArray::new(tags)?;
+        unsafe { ffi::ossia_node_set_tags(self.0, tags.as_ptr(), tags.len() as ffi::size_t) };
+        Ok(())
+    }
 
     pub fn hidden(&self) -> bool {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_get_hidden(self.0) != 0 }
     }
 
     pub fn set_hidden(&mut self, hidden: bool) {
-        todo!()
-    }
-
-    pub fn refresh_rate(&self) -> i32 {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_set_hidden(self.0, hidden as c_int) }
+    }
+
+    pub fn refresh_rate(&self) -> Option<i32> {
+        let _tree = self.1.lock_tree();
+        let mut ok: c_int = 0;
+        let rate = unsafe { ffi::ossia_node_get_refresh_rate(self.0, &mut ok) };
+        if ok != 0 {
+            Some(rate)
+        } else {
+            None
+        }
     }
 
     pub fn set_refresh_rate(&mut self, rate: i32) {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_set_refresh_rate(self.0, rate) }
     }
 
     pub fn unset_refresh_rate(&mut self) {
-        todo!()
-    }
-
-    pub fn priority(&self) -> f32 {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_unset_refresh_rate(self.0) }
+    }
+
+    pub fn priority(&self) -> Option<f32> {
+        let _tree = self.1.lock_tree();
+        let mut ok: c_int = 0;
+        let priority = unsafe { ffi::ossia_node_get_priority(self.0, &mut ok) };
+        if ok != 0 {
+            Some(priority)
+        } else {
+            None
+        }
     }
 
     pub fn set_priority(&mut self, priority: f32) {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_set_priority(self.0, priority) }
     }
 
     pub fn unset_priority(&mut self) {
-        todo!()
-    }
-
-    pub fn step_size(&self) -> f64 {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_unset_priority(self.0) }
+    }
+
+    pub fn step_size(&self) -> Option<f64> {
+        let _tree = self.1.lock_tree();
+        let mut ok: c_int = 0;
+        let step_size = unsafe { ffi::ossia_node_get_value_step_size(self.0, &mut ok) };
+        if ok != 0 {
+            Some(step_size)
+        } else {
+            None
+        }
     }
 
     pub fn set_step_size(&mut self, step_size: f64) {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_set_value_step_size(self.0, step_size) }
     }
 
     pub fn unset_step_size(&mut self) {
-        todo!()
-    }
-
-    pub fn instance_bounds(&self) -> Range<i32> {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_unset_value_step_size(self.0) }
+    }
+
+    pub fn instance_bounds(&self) -> Option<Range<i32>> {
+        let _tree = self.1.lock_tree();
+        let mut min: c_int = 0;
+        let mut max: c_int = 0;
+        let mut ok: c_int = 0;
+        unsafe { ffi::ossia_node_get_instance_bounds(self.0, &mut min, &mut max, &mut ok) };
+        if ok != 0 {
+            Some(min..max)
+        } else {
+            None
+        }
     }
 
     pub fn set_instance_bounds(&mut self, instance_bounds: Range<i32>) {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe {
+            ffi::ossia_node_set_instance_bounds(self.0, instance_bounds.start, instance_bounds.end)
+        }
     }
 
     pub fn unset_instance_bounds(&mut self) {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_unset_instance_bounds(self.0) }
     }
 
-    pub fn default_value(&self) -> Value {
-        todo!()
+    pub fn default_value(&self) -> Option<Value> {
+        let _tree = self.1.lock_tree();
+        let value = unsafe { ffi::ossia_node_get_default_value(self.0) };
+        if value.is_null() {
+            None
+        } else {
+            Some(Value(value))
+        }
     }
 
     pub fn set_default_value(&mut self, value: Value) {
-        todo!()
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_node_set_default_value(self.0, value.0) }
     }
 }
+
+/// Filters device-wide node events down to the subtree under `root`.
+fn below<F>(root: Address, mut cb: F) -> impl FnMut(Node) + Send + 'static
+where
+    F: FnMut(Address, Node) + Send + 'static,
+{
+    move |node: Node| {
+        if let Ok(address) = node.address() {
+            if root.is_ancestor_of(&address) {
+                cb(address, node)
+            }
+        }
+    }
+}
+
+/// Copies and frees a node array returned by `ossia_node_find_pattern` or `ossia_node_create_pattern`.
+pub(crate) unsafe fn take_nodes(
+    ptr: *mut ffi::ossia_node_t,
+    size: ffi::size_t,
+    device: &Device,
+) -> Vec<Node> {
+    if ptr.is_null() {
+        return Vec::new();
+    }
+
+    let nodes = std::slice::from_raw_parts(ptr, size as usize)
+        .iter()
+        .map(|&n| Node(n, device.clone()))
+        .collect();
+    ffi::ossia_node_array_free(ptr);
+    nodes
+}
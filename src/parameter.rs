@@ -1,20 +1,23 @@
-use crate::Domain;
 use crate::Node;
 use crate::{
     ffi::{self, ossia_access_mode, ossia_bounding_mode, ossia_type},
-    Value,
+    string::{c_string, take_string},
+    subscription::{self, value_trampoline},
+    value::{copy_fn, copy_in, take_list},
+    Coerce, ConversionError, Result, Subscription, Value,
 };
+use crate::{Device, Domain};
 use enum_repr::EnumRepr;
-use libffi::high::Closure2;
 use num_enum::TryFromPrimitive;
 use std::{
-    convert::{TryFrom, TryInto},
+    convert::TryFrom,
     ffi::{c_void, CStr},
     os::raw::{c_char, c_int},
+    sync::Mutex,
 };
 
 #[EnumRepr(type = "ossia_type")]
-#[derive(TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
 pub enum Type {
     Float = ffi::ossia_type_FLOAT_T,
     Int = ffi::ossia_type_INT_T,
@@ -28,8 +31,65 @@ pub enum Type {
     Char = ffi::ossia_type_CHAR_T,
 }
 
+/// Rust types that map to a parameter [`Type`].
+pub trait ValueType {
+    const TYPE: Type;
+}
+
+impl ValueType for () {
+    const TYPE: Type = Type::Impulse;
+}
+
+impl ValueType for i32 {
+    const TYPE: Type = Type::Int;
+}
+
+impl ValueType for f32 {
+    const TYPE: Type = Type::Float;
+}
+
+impl ValueType for (f32, f32) {
+    const TYPE: Type = Type::Vec2f;
+}
+
+impl ValueType for (f32, f32, f32) {
+    const TYPE: Type = Type::Vec3f;
+}
+
+impl ValueType for (f32, f32, f32, f32) {
+    const TYPE: Type = Type::Vec4f;
+}
+
+impl ValueType for bool {
+    const TYPE: Type = Type::Bool;
+}
+
+impl ValueType for c_char {
+    const TYPE: Type = Type::Char;
+}
+
+impl ValueType for String {
+    const TYPE: Type = Type::String;
+}
+
+impl ValueType for Vec<u8> {
+    const TYPE: Type = Type::String;
+}
+
+impl ValueType for Vec<Value> {
+    const TYPE: Type = Type::List;
+}
+
+impl ValueType for Vec<f32> {
+    const TYPE: Type = Type::List;
+}
+
+impl ValueType for Vec<i32> {
+    const TYPE: Type = Type::List;
+}
+
 #[EnumRepr(type = "ossia_access_mode")]
-#[derive(TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
 pub enum Access {
     Bi = ffi::ossia_access_mode_BI,
     Get = ffi::ossia_access_mode_GET,
@@ -37,7 +97,7 @@ pub enum Access {
 }
 
 #[EnumRepr(type = "ossia_bounding_mode")]
-#[derive(TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
 pub enum Bounding {
     Free = ffi::ossia_bounding_mode_FREE,
     Clip = ffi::ossia_bounding_mode_CLIP,
@@ -47,80 +107,100 @@ pub enum Bounding {
     High = ffi::ossia_bounding_mode_HIGH,
 }
 
-pub struct Parameter(pub(crate) ffi::ossia_node_t);
-
-pub struct ValueCallbackIdx(pub(crate) ffi::ossia_value_callback_idx_t);
+/// The parameter of a node, it keeps its device alive.
+#[derive(Clone)]
+pub struct Parameter(pub(crate) ffi::ossia_parameter_t, pub(crate) Device);
 
 impl Parameter {
-    pub fn node(&self) -> Node {
-        Node(unsafe { ffi::ossia_parameter_get_node(self.0) })
+    pub fn node(&self) -> Result<Node> {
+        let _tree = self.1.lock_tree();
+        Node::from_raw(unsafe { ffi::ossia_parameter_get_node(self.0) }, &self.1)
     }
 
     pub fn set_access_mode(&mut self, am: Access) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_access_mode(self.0, am as ossia_access_mode) }
     }
 
-    pub fn get_access_mode(&self) -> Access {
-        Access::try_from(unsafe { ffi::ossia_parameter_get_access_mode(self.0) as isize }).unwrap()
+    pub fn get_access_mode(&self) -> Result<Access> {
+        let _tree = self.1.lock_tree();
+        Ok(Access::try_from(unsafe {
+            ffi::ossia_parameter_get_access_mode(self.0) as isize
+        })?)
     }
 
     pub fn set_bounding_mode(&mut self, bm: Bounding) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_bounding_mode(self.0, bm as ossia_bounding_mode) }
     }
 
-    pub fn get_bounding_mode(&self) -> Bounding {
-        Bounding::try_from(unsafe { ffi::ossia_parameter_get_bounding_mode(self.0) as isize })
-            .unwrap()
+    pub fn get_bounding_mode(&self) -> Result<Bounding> {
+        let _tree = self.1.lock_tree();
+        Ok(Bounding::try_from(unsafe {
+            ffi::ossia_parameter_get_bounding_mode(self.0) as isize
+        })?)
     }
 
     pub fn set_domain(&mut self, domain: Domain) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_domain(self.0, domain.0) }
     }
 
     pub fn get_domain(&self) -> Domain {
+        let _tree = self.1.lock_tree();
         Domain(unsafe { ffi::ossia_parameter_get_domain(self.0) })
     }
 
-    pub fn set_unit(&mut self, unit: &str) {
-        unsafe { ffi::ossia_parameter_set_unit(self.0, unit.as_ptr() as *const c_char) }
+    pub fn set_unit(&mut self, unit: &str) -> Result<()> {
+        let _tree = self.1.lock_tree();
+        let unit = c_string(unit)?;
+        unsafe { ffi::ossia_parameter_set_unit(self.0, unit.as_ptr()) };
+        Ok(())
     }
 
-    pub fn get_unit(&self) -> &str {
-        unsafe { CStr::from_ptr(ffi::ossia_parameter_get_unit(self.0)) }
-            .to_str()
-            .unwrap()
+    pub fn get_unit(&self) -> Result<String> {
+        let _tree = self.1.lock_tree();
+        unsafe { take_string(ffi::ossia_parameter_get_unit(self.0)) }
     }
 
     pub fn set_muted(&mut self, muted: bool) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_muted(self.0, muted as c_int) }
     }
 
     pub fn get_muted(&self) -> bool {
-        unsafe { ffi::ossia_parameter_get_muted(self.0) == 0 }
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_parameter_get_muted(self.0) != 0 }
     }
 
     pub fn set_disabled(&mut self, disabled: bool) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_disabled(self.0, disabled as c_int) }
     }
 
     pub fn get_disabled(&self) -> bool {
-        unsafe { ffi::ossia_parameter_get_disabled(self.0) == 0 }
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_parameter_get_disabled(self.0) != 0 }
     }
 
     pub fn set_critical(&mut self, critical: bool) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_critical(self.0, critical as c_int) }
     }
 
     pub fn get_critical(&self) -> bool {
-        unsafe { ffi::ossia_parameter_get_critical(self.0) == 0 }
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_parameter_get_critical(self.0) != 0 }
     }
 
     pub fn set_repetition_filter(&mut self, repetition_filter: bool) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_repetition_filter(self.0, repetition_filter as c_int) }
     }
 
     pub fn get_repetition_filter(&self) -> bool {
-        unsafe { ffi::ossia_parameter_get_repetition_filter(self.0) == 0 }
+        let _tree = self.1.lock_tree();
+        unsafe { ffi::ossia_parameter_get_repetition_filter(self.0) != 0 }
     }
 
     pub fn set_value(&mut self, value: Value) {
@@ -131,119 +211,259 @@ impl Parameter {
         Value(unsafe { ffi::ossia_parameter_get_value(self.0) })
     }
 
+    /// Asks the remote side for the current value, blocking until it answers.
+    pub fn fetch_value(&self) -> Value {
+        Value(unsafe { ffi::ossia_parameter_fetch_value(self.0) })
+    }
+
     pub fn set_listening(&mut self, listening: bool) {
+        let _tree = self.1.lock_tree();
         unsafe { ffi::ossia_parameter_set_listening(self.0, listening as c_int) };
     }
 
-    pub fn add_callback<F>(&mut self, cb: F, ctx: *mut c_void) -> ValueCallbackIdx
+    /// Calls `cb` with every new value until the returned subscription is dropped.
+    ///
+    /// libossia runs the callbacks on the thread that set the value. A value pushed to this
+    /// parameter from within `cb` does not call `cb` again, and dropping the subscription from
+    /// within `cb` only frees it once `cb` returns. A panic in `cb` is caught before it reaches
+    /// libossia and `cb` keeps being called afterwards.
+    pub fn add_callback<F>(&mut self, cb: F) -> Subscription
     where
-        F: Fn(*mut c_void, ffi::ossia_value_t),
+        F: FnMut(Value) + Send + 'static,
     {
-        let closure = Closure2::new(&cb);
-
-        ValueCallbackIdx(unsafe {
-            ffi::ossia_parameter_add_callback(self.0, Some(*closure.code_ptr()), ctx)
-        })
+        subscription::on_value(self, cb)
     }
 
-    pub fn push_callback<F>(&mut self, cb: F, ctx: *mut c_void)
+    /// Calls `cb` with every new value converted to `T`, values of another type go to `on_error`.
+    ///
+    /// The callbacks can push to this parameter and drop the subscription as with
+    /// [`add_callback`](Parameter::add_callback).
+    pub fn on_value<T, F, E>(&mut self, mut cb: F, mut on_error: E) -> Subscription
     where
-        F: Fn(*mut c_void, ffi::ossia_value_t),
+        T: for<'a> TryFrom<&'a Value, Error = ConversionError> + 'static,
+        F: FnMut(T) + Send + 'static,
+        E: FnMut(ConversionError) + Send + 'static,
     {
-        let closure = Closure2::new(&cb);
-        unsafe { ffi::ossia_parameter_add_callback(self.0, Some(*closure.code_ptr()), ctx) };
+        self.add_callback(move |value: Value| match T::try_from(&value) {
+            Ok(value) => cb(value),
+            Err(e) => on_error(e),
+        })
     }
 
-    pub fn rm_callback(&mut self, index: ValueCallbackIdx) {
-        unsafe { ffi::ossia_parameter_remove_callback(self.0, index.0) };
+    /// Registers `cb` for the whole lifetime of the process, it can never be removed.
+    pub fn push_callback<F>(&mut self, cb: F)
+    where
+        F: FnMut(Value) + Send + 'static,
+    {
+        let ctx = Box::into_raw(Box::new(Mutex::new(cb))) as *mut c_void;
+        unsafe { ffi::ossia_parameter_push_callback(self.0, Some(value_trampoline::<F>), ctx) };
     }
 }
 
-impl Into<i32> for Parameter {
-    fn into(self) -> i32 {
+impl Coerce<i32> for Parameter {
+    fn coerce(&self) -> i32 {
         unsafe { ffi::ossia_parameter_to_int(self.0) }
     }
 }
 
-impl Into<f32> for Parameter {
-    fn into(self) -> f32 {
+impl Coerce<f32> for Parameter {
+    fn coerce(&self) -> f32 {
         unsafe { ffi::ossia_parameter_to_float(self.0) }
     }
 }
 
-impl Into<(f32, f32)> for Parameter {
-    fn into(self) -> (f32, f32) {
+impl Coerce<(f32, f32)> for Parameter {
+    fn coerce(&self) -> (f32, f32) {
         let o = unsafe { ffi::ossia_parameter_to_2f(self.0) };
         (o.val[0], o.val[1])
     }
 }
 
-impl Into<(f32, f32, f32)> for Parameter {
-    fn into(self) -> (f32, f32, f32) {
+impl Coerce<(f32, f32, f32)> for Parameter {
+    fn coerce(&self) -> (f32, f32, f32) {
         let o = unsafe { ffi::ossia_parameter_to_3f(self.0) };
         (o.val[0], o.val[1], o.val[2])
     }
 }
 
-impl Into<(f32, f32, f32, f32)> for Parameter {
-    fn into(self) -> (f32, f32, f32, f32) {
+impl Coerce<(f32, f32, f32, f32)> for Parameter {
+    fn coerce(&self) -> (f32, f32, f32, f32) {
         let o = unsafe { ffi::ossia_parameter_to_4f(self.0) };
         (o.val[0], o.val[1], o.val[2], o.val[3])
     }
 }
 
-impl Into<bool> for Parameter {
-    fn into(self) -> bool {
-        unsafe { ffi::ossia_parameter_to_bool(self.0) == 0 }
+impl Coerce<bool> for Parameter {
+    fn coerce(&self) -> bool {
+        unsafe { ffi::ossia_parameter_to_bool(self.0) != 0 }
     }
 }
 
-impl Into<c_char> for Parameter {
-    fn into(self) -> c_char {
+impl Coerce<c_char> for Parameter {
+    fn coerce(&self) -> c_char {
         unsafe { ffi::ossia_parameter_to_char(self.0) }
     }
 }
 
-impl Into<Vec<u8>> for Parameter {
-    fn into(self) -> Vec<u8> {
+impl Coerce<Vec<u8>> for Parameter {
+    fn coerce(&self) -> Vec<u8> {
         let mut ptr: *mut c_char = std::ptr::null_mut();
         let mut size: ffi::size_t = 0;
         unsafe { ffi::ossia_parameter_to_byte_array(self.0, &mut ptr, &mut size) };
 
-        unsafe {
-            Vec::from_raw_parts(
-                ptr as *mut u8,
-                size.try_into().unwrap(),
-                size.try_into().unwrap(),
-            )
+        if ptr.is_null() {
+            return Vec::new();
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, size as usize) }.to_vec();
+        unsafe { ffi::ossia_string_free(ptr) };
+        bytes
+    }
+}
+
+impl Coerce<String> for Parameter {
+    fn coerce(&self) -> String {
+        let ptr = unsafe { ffi::ossia_parameter_to_string(self.0) };
+        if ptr.is_null() {
+            return String::new();
         }
+
+        let s = unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { ffi::ossia_string_free(ptr as *mut c_char) };
+        s
     }
 }
 
-impl Into<String> for Parameter {
-    fn into(self) -> String {
-        unsafe { CStr::from_ptr(ffi::ossia_parameter_to_string(self.0)) }
-            .to_str()
-            .unwrap()
-            .to_owned()
+impl Coerce<Vec<Value>> for Parameter {
+    fn coerce(&self) -> Vec<Value> {
+        let mut ptr: *mut ffi::ossia_value_t = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_parameter_to_list(self.0, &mut ptr, &mut size) };
+        unsafe { take_list(ptr, size) }
     }
 }
 
-impl Into<Vec<Parameter>> for Parameter {
-    fn into(self) -> Vec<Parameter> {
-        todo!()
+impl Coerce<Vec<f32>> for Parameter {
+    fn coerce(&self) -> Vec<f32> {
+        let mut ptr: *mut f32 = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_parameter_to_fn(self.0, &mut ptr, &mut size) };
+        unsafe { copy_fn(ptr, size) }
     }
 }
 
-impl Into<Vec<f32>> for Parameter {
-    fn into(self) -> Vec<f32> {
-        todo!()
+impl Coerce<Vec<i32>> for Parameter {
+    fn coerce(&self) -> Vec<i32> {
+        let mut ptr: *mut c_int = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_parameter_to_in(self.0, &mut ptr, &mut size) };
+        unsafe { copy_in(ptr, size) }
     }
 }
 
-impl Into<Vec<i32>> for Parameter {
-    fn into(self) -> Vec<i32> {
-        todo!()
+impl TryFrom<&Parameter> for () {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<(), ConversionError> {
+        <()>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for i32 {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<i32, ConversionError> {
+        i32::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for f32 {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<f32, ConversionError> {
+        f32::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for (f32, f32) {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<(f32, f32), ConversionError> {
+        <(f32, f32)>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for (f32, f32, f32) {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<(f32, f32, f32), ConversionError> {
+        <(f32, f32, f32)>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for (f32, f32, f32, f32) {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<(f32, f32, f32, f32), ConversionError> {
+        <(f32, f32, f32, f32)>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for bool {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<bool, ConversionError> {
+        bool::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for c_char {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<c_char, ConversionError> {
+        c_char::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for Vec<u8> {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<Vec<u8>, ConversionError> {
+        <Vec<u8>>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for String {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<String, ConversionError> {
+        String::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for Vec<Value> {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<Vec<Value>, ConversionError> {
+        <Vec<Value>>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for Vec<f32> {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<Vec<f32>, ConversionError> {
+        <Vec<f32>>::try_from(&param.get_value())
+    }
+}
+
+impl TryFrom<&Parameter> for Vec<i32> {
+    type Error = ConversionError;
+
+    fn try_from(param: &Parameter) -> Result<Vec<i32>, ConversionError> {
+        <Vec<i32>>::try_from(&param.get_value())
     }
 }
 
@@ -0,0 +1,306 @@
+use crate::{Address, Error, Result};
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnySequence,
+    /// `[a-z]` or `[!a-z]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    /// `{foo,bar}`
+    Choice(Vec<Vec<char>>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Tokens(Vec<Token>),
+    /// `//`, any number of segments including none
+    Descendants,
+}
+
+/// OSC 1.1 address pattern, e.g. `/synth/osc.[1-4]/{freq,gain}` or `//gain`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressPattern {
+    pattern: String,
+    segments: Vec<Segment>,
+}
+
+fn is_reserved(c: char) -> bool {
+    c.is_whitespace() || c.is_control() || matches!(c, '#' | ',' | '/')
+}
+
+fn parse_class(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Token> {
+    let negated = chars.peek() == Some(&'!');
+    if negated {
+        chars.next();
+    }
+
+    let mut ranges = Vec::new();
+    loop {
+        let lo = match chars.next()? {
+            ']' if !ranges.is_empty() => return Some(Token::Class { negated, ranges }),
+            c if is_reserved(c) || matches!(c, '[' | ']') => return None,
+            c => c,
+        };
+
+        let mut lookahead = chars.clone();
+        match (lookahead.next(), lookahead.next()) {
+            (Some('-'), Some(hi)) if hi != ']' => {
+                if is_reserved(hi) || hi == '[' || hi < lo {
+                    return None;
+                }
+                chars.next();
+                chars.next();
+                ranges.push((lo, hi));
+            }
+            _ => ranges.push((lo, lo)),
+        }
+    }
+}
+
+fn parse_choice(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Token> {
+    let mut choices = vec![Vec::new()];
+    loop {
+        match chars.next()? {
+            '}' => return Some(Token::Choice(choices)),
+            ',' => choices.push(Vec::new()),
+            c if is_reserved(c) || matches!(c, '*' | '?' | '[' | ']' | '{') => return None,
+            c => choices.last_mut()?.push(c),
+        }
+    }
+}
+
+fn parse_segment(segment: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = segment.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => Token::AnySequence,
+            '?' => Token::AnyChar,
+            '[' => parse_class(&mut chars)?,
+            '{' => parse_choice(&mut chars)?,
+            ']' | '}' => return None,
+            c if is_reserved(c) => return None,
+            c => Token::Literal(c),
+        });
+    }
+    Some(tokens)
+}
+
+/// Whether `tokens` match the whole of `s`.
+///
+/// Works backwards from the last token, keeping for each position of `s` whether the tokens
+/// already processed match from there on, so `*` never backtracks.
+fn match_tokens(tokens: &[Token], s: &[char]) -> bool {
+    let mut next = vec![false; s.len() + 1];
+    next[s.len()] = true;
+
+    for token in tokens.iter().rev() {
+        let mut current = vec![false; s.len() + 1];
+        for i in (0..=s.len()).rev() {
+            current[i] = match token {
+                Token::Literal(c) => s.get(i) == Some(c) && next[i + 1],
+                Token::AnyChar => i < s.len() && next[i + 1],
+                // either nothing more is consumed, or one character and the `*` again
+                Token::AnySequence => next[i] || (i < s.len() && current[i + 1]),
+                Token::Class { negated, ranges } => match s.get(i) {
+                    Some(c) => {
+                        let inside = ranges.iter().any(|&(lo, hi)| lo <= *c && *c <= hi);
+                        inside != *negated && next[i + 1]
+                    }
+                    None => false,
+                },
+                Token::Choice(choices) => choices
+                    .iter()
+                    .any(|choice| s[i..].starts_with(choice) && next[i + choice.len()]),
+            };
+        }
+        next = current;
+    }
+
+    next[0]
+}
+
+/// Whether `segments` match the whole of `parts`, the same way `match_tokens` does.
+fn match_segments(segments: &[Segment], parts: &[Vec<char>]) -> bool {
+    let mut next = vec![false; parts.len() + 1];
+    next[parts.len()] = true;
+
+    for segment in segments.iter().rev() {
+        let mut current = vec![false; parts.len() + 1];
+        for i in (0..=parts.len()).rev() {
+            current[i] = match segment {
+                Segment::Descendants => next[i] || (i < parts.len() && current[i + 1]),
+                Segment::Tokens(tokens) => {
+                    i < parts.len() && next[i + 1] && match_tokens(tokens, &parts[i])
+                }
+            };
+        }
+        next = current;
+    }
+
+    next[0]
+}
+
+impl AddressPattern {
+    pub fn new(pattern: &str) -> Result<AddressPattern> {
+        let invalid = || Error::InvalidPattern(pattern.to_owned());
+        let rest = pattern.strip_prefix('/').ok_or_else(invalid)?;
+
+        let mut segments = Vec::new();
+        if !rest.is_empty() {
+            let parts: Vec<&str> = rest.split('/').collect();
+            for (i, part) in parts.iter().enumerate() {
+                if part.is_empty() {
+                    // an empty segment comes from `//` and must be followed by a named one
+                    let last = i + 1 == parts.len();
+                    if last || segments.last() == Some(&Segment::Descendants) {
+                        return Err(invalid());
+                    }
+                    segments.push(Segment::Descendants);
+                } else {
+                    segments.push(Segment::Tokens(parse_segment(part).ok_or_else(invalid)?));
+                }
+            }
+        }
+
+        Ok(AddressPattern {
+            pattern: pattern.to_owned(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, address: &Address) -> bool {
+        let parts: Vec<Vec<char>> = address.segments().map(|s| s.chars().collect()).collect();
+        match_segments(&self.segments, &parts)
+    }
+}
+
+impl fmt::Display for AddressPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl FromStr for AddressPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<AddressPattern> {
+        AddressPattern::new(s)
+    }
+}
+
+impl AsRef<AddressPattern> for AddressPattern {
+    fn as_ref(&self) -> &AddressPattern {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, address: &str) -> bool {
+        AddressPattern::new(pattern)
+            .unwrap()
+            .matches(&Address::new(address).unwrap())
+    }
+
+    #[test]
+    fn literals() {
+        assert!(matches("/", "/"));
+        assert!(matches("/synth/freq", "/synth/freq"));
+        assert!(!matches("/synth/freq", "/synth/gain"));
+        assert!(!matches("/synth", "/synth/freq"));
+        assert!(!matches("/synth/freq", "/synth"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("/osc.?", "/osc.1"));
+        assert!(!matches("/osc.?", "/osc."));
+        assert!(!matches("/osc.?", "/osc.12"));
+        assert!(matches("/*", "/synth"));
+        assert!(matches("/syn*", "/syn"));
+        assert!(matches("/*th*q", "/synthfreq"));
+        assert!(!matches("/*", "/synth/freq"));
+        assert!(matches("/*/freq", "/synth/freq"));
+    }
+
+    #[test]
+    fn many_stars_do_not_backtrack() {
+        let address = format!("/{}", "a".repeat(64));
+        let pattern = format!("/{}b", "*a".repeat(32));
+        assert!(!matches(&pattern, &address));
+        assert!(matches(&format!("/{}", "*a".repeat(32)), &address));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("/osc.[1-4]", "/osc.3"));
+        assert!(!matches("/osc.[1-4]", "/osc.5"));
+        assert!(matches("/[a-z]", "/q"));
+        assert!(!matches("/[a-z]", "/Q"));
+        assert!(matches("/[abc]", "/b"));
+        assert!(matches("/[!a-z]", "/Q"));
+        assert!(!matches("/[!a-z]", "/q"));
+        assert!(matches("/[!ab]", "/c"));
+        assert!(!matches("/[!ab]", "/a"));
+    }
+
+    #[test]
+    fn dashes_at_the_ends_of_a_class_are_literal() {
+        assert!(matches("/[-a]", "/-"));
+        assert!(matches("/[-a]", "/a"));
+        assert!(!matches("/[-a]", "/b"));
+        assert!(matches("/[a-]", "/-"));
+        assert!(matches("/[a-]", "/a"));
+        assert!(!matches("/[a-]", "/b"));
+    }
+
+    #[test]
+    fn choices() {
+        assert!(matches("/{freq,gain}", "/freq"));
+        assert!(matches("/{freq,gain}", "/gain"));
+        assert!(!matches("/{freq,gain}", "/pan"));
+        assert!(matches("/osc{a,}", "/osca"));
+        assert!(matches("/osc{a,}", "/osc"));
+        assert!(!matches("/osc{a,}", "/oscb"));
+    }
+
+    #[test]
+    fn descendants() {
+        assert!(matches("//gain", "/gain"));
+        assert!(matches("//gain", "/synth/gain"));
+        assert!(matches("//gain", "/synth/osc.1/gain"));
+        assert!(!matches("//gain", "/synth/gain/smooth"));
+        assert!(matches("/synth//gain", "/synth/osc.1/gain"));
+        assert!(!matches("/synth//gain", "/fx/gain"));
+        assert!(matches("//osc.*//gain", "/synth/osc.1/env/gain"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for invalid in &[
+            "", "synth", "/[]", "/[!]", "/[a", "/a]", "/{a", "/a}", "/[z-a]", "/a/", "///",
+            "/a///b", "/a b", "/a#", "/{a,b*}",
+        ] {
+            assert_eq!(
+                AddressPattern::new(invalid),
+                Err(Error::InvalidPattern((*invalid).to_owned())),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+}
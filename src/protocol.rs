@@ -1,47 +1,208 @@
 #![allow(dead_code)]
 
-use std::os::raw::c_char;
-
-use crate::ffi;
+use crate::{ffi, string::c_string, Error, Result};
+use std::net::SocketAddr;
 
 pub struct Protocol(pub(crate) ffi::ossia_protocol_t);
 
 impl Protocol {
-    pub fn multiplex(local: Protocol, other: Protocol) -> Protocol {
-        let protocol = Protocol(unsafe { ffi::ossia_protocol_multiplex_create() });
-        unsafe { ffi::ossia_protocol_multiplex_expose_to(local.0, other.0) };
+    fn from_raw(protocol: ffi::ossia_protocol_t) -> Result<Protocol> {
+        if protocol.is_null() {
+            Err(Error::ProtocolCreation)
+        } else {
+            Ok(Protocol(protocol))
+        }
+    }
 
+    /// Gives up ownership of the handle, whoever receives it is responsible for freeing it.
+    pub(crate) fn into_raw(self) -> ffi::ossia_protocol_t {
+        let protocol = self.0;
+        std::mem::forget(self);
         protocol
     }
 
-    pub fn osc(ip: &str, remote_port: i32, local_port: i32) -> Protocol {
-        Protocol(unsafe {
-            ffi::ossia_protocol_osc_create(ip.as_ptr() as *const c_char, remote_port, local_port)
+    /// Exposes a device through both `local` and `other`, see `MultiplexProtocol` for more.
+    pub fn multiplex(local: Protocol, other: Protocol) -> Result<Protocol> {
+        let mut multiplex = MultiplexProtocol::new()?;
+        multiplex.add(local);
+        multiplex.add(other);
+
+        Ok(multiplex.into())
+    }
+}
+
+/// A protocol exposing a device through every protocol added to it.
+///
+/// Protocols can only be added before the device is created with it. The device takes the
+/// multiplex over, and ossia-c offers no way to reach it or to remove a protocol from it later.
+pub struct MultiplexProtocol(Protocol);
+
+impl MultiplexProtocol {
+    pub fn new() -> Result<Self> {
+        Ok(MultiplexProtocol(Protocol::from_raw(unsafe {
+            ffi::ossia_protocol_multiplex_create()
+        })?))
+    }
+
+    /// Also exposes the device through `protocol`, which the multiplex takes over.
+    pub fn add(&mut self, protocol: Protocol) {
+        unsafe { ffi::ossia_protocol_multiplex_expose_to((self.0).0, protocol.into_raw()) }
+    }
+}
+
+impl From<MultiplexProtocol> for Protocol {
+    fn from(multiplex: MultiplexProtocol) -> Protocol {
+        multiplex.0
+    }
+}
+
+/// A plain OSC protocol sending to `remote` and listening on `local_port`.
+pub struct OscConfig {
+    remote: SocketAddr,
+    local_port: u16,
+}
+
+impl OscConfig {
+    pub fn new(remote: SocketAddr, local_port: u16) -> Self {
+        OscConfig { remote, local_port }
+    }
+
+    pub fn build(&self) -> Result<Protocol> {
+        check_remote(self.remote)?;
+        check_port(self.local_port)?;
+
+        let ip = c_string(&self.remote.ip().to_string())?;
+        Protocol::from_raw(unsafe {
+            ffi::ossia_protocol_osc_create(
+                ip.as_ptr(),
+                self.remote.port().into(),
+                self.local_port.into(),
+            )
         })
     }
+}
+
+/// A Minuit protocol announcing itself as `local_name` to `remote`.
+pub struct MinuitConfig {
+    local_name: String,
+    remote: SocketAddr,
+    local_port: u16,
+}
+
+impl MinuitConfig {
+    pub fn new(local_name: &str, remote: SocketAddr, local_port: u16) -> Self {
+        MinuitConfig {
+            local_name: local_name.to_owned(),
+            remote,
+            local_port,
+        }
+    }
+
+    pub fn build(&self) -> Result<Protocol> {
+        if self.local_name.is_empty() {
+            return Err(Error::InvalidConfig("the local name is empty"));
+        }
+        check_remote(self.remote)?;
+        check_port(self.local_port)?;
 
-    pub fn minuit(local_name: &str, ip: &str, remote_port: i32, local_port: i32) -> Protocol {
-        Protocol(unsafe {
+        let local_name = c_string(&self.local_name)?;
+        let ip = c_string(&self.remote.ip().to_string())?;
+        Protocol::from_raw(unsafe {
             ffi::ossia_protocol_minuit_create(
-                local_name.as_ptr() as *const c_char,
-                ip.as_ptr() as *const c_char,
-                remote_port,
-                local_port,
+                local_name.as_ptr(),
+                ip.as_ptr(),
+                self.remote.port().into(),
+                self.local_port.into(),
             )
         })
     }
+}
+
+/// An OSCQuery server, listening on the libossia default ports unless told otherwise.
+pub struct OscQueryServerConfig {
+    osc_port: u16,
+    ws_port: u16,
+}
+
+impl OscQueryServerConfig {
+    pub fn new() -> Self {
+        OscQueryServerConfig {
+            osc_port: 1234,
+            ws_port: 5678,
+        }
+    }
+
+    pub fn osc_port(mut self, port: u16) -> Self {
+        self.osc_port = port;
+        self
+    }
 
-    pub fn oscquery_server(osc_port: i32, ws_port: i32) -> Protocol {
-        Protocol(unsafe { ffi::ossia_protocol_oscquery_server_create(osc_port, ws_port) })
+    pub fn ws_port(mut self, port: u16) -> Self {
+        self.ws_port = port;
+        self
     }
 
-    pub fn oscquery_mirror(host: &str) -> Protocol {
-        Protocol(unsafe {
-            ffi::ossia_protocol_oscquery_mirror_create(host.as_ptr() as *const c_char)
+    pub fn build(&self) -> Result<Protocol> {
+        check_port(self.osc_port)?;
+        check_port(self.ws_port)?;
+        if self.osc_port == self.ws_port {
+            return Err(Error::InvalidConfig(
+                "the OSC and WebSocket ports are the same",
+            ));
+        }
+
+        Protocol::from_raw(unsafe {
+            ffi::ossia_protocol_oscquery_server_create(self.osc_port.into(), self.ws_port.into())
         })
     }
 }
 
+impl Default for OscQueryServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An OSCQuery client mirroring the namespace of the server whose WebSocket listens on `server`.
+pub struct OscQueryMirrorConfig {
+    server: SocketAddr,
+}
+
+impl OscQueryMirrorConfig {
+    pub fn new(server: SocketAddr) -> Self {
+        OscQueryMirrorConfig { server }
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    pub fn build(&self) -> Result<Protocol> {
+        check_remote(self.server)?;
+
+        let host = c_string(&format!("ws://{}", self.server))?;
+        Protocol::from_raw(unsafe { ffi::ossia_protocol_oscquery_mirror_create(host.as_ptr()) })
+    }
+}
+
+fn check_port(port: u16) -> Result<()> {
+    if port == 0 {
+        Err(Error::InvalidConfig("port 0 cannot be listened on"))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_remote(remote: SocketAddr) -> Result<()> {
+    if remote.ip().is_unspecified() {
+        Err(Error::InvalidConfig("the remote address is unspecified"))
+    } else if remote.port() == 0 {
+        Err(Error::InvalidConfig("the remote port is 0"))
+    } else {
+        Ok(())
+    }
+}
+
 impl Drop for Protocol {
     fn drop(&mut self) {
         unsafe { ffi::ossia_protocol_free(self.0) }
@@ -0,0 +1,104 @@
+use crate::{
+    buffer::Bounded, subscription, Error, OverflowPolicy, OwnedValue, Parameter, Result,
+    Subscription, Value,
+};
+use futures_core::Stream;
+use std::{
+    convert::TryFrom,
+    future::Future,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// The values received by a parameter, the callback is removed when the stream is dropped.
+///
+/// The stream ends once the parameter is deleted and the values buffered before have been
+/// polled.
+pub struct ValueStream {
+    buffer: Arc<Bounded<OwnedValue>>,
+    _subscriptions: [Subscription; 2],
+}
+
+impl ValueStream {
+    /// Buffers at most `capacity` values that have not been polled yet, 64 by default.
+    pub fn capacity(self, capacity: usize) -> Self {
+        self.buffer.lock().capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets what happens once the buffer is full, the oldest values are dropped by default.
+    ///
+    /// Every value comes from the same parameter, so `CoalescePerParameter` replaces the newest
+    /// pending value.
+    pub fn overflow(self, overflow: OverflowPolicy) -> Self {
+        self.buffer.lock().policy = overflow;
+        self
+    }
+}
+
+impl Stream for ValueStream {
+    type Item = OwnedValue;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<OwnedValue>> {
+        self.buffer.poll_pop(cx)
+    }
+}
+
+impl Drop for ValueStream {
+    fn drop(&mut self) {
+        // releases the callback blocked on a full buffer before it gets unregistered
+        self.buffer.close();
+    }
+}
+
+impl Parameter {
+    /// Streams every value received from now on.
+    pub fn stream(&mut self) -> ValueStream {
+        let buffer = Arc::new(Bounded::new(64, OverflowPolicy::DropOldest));
+
+        let values = buffer.clone();
+        let on_value = subscription::on_value(self, move |value: Value| {
+            // values libossia cannot describe are not worth waking anyone up for
+            if let Ok(value) = OwnedValue::try_from(value) {
+                values.push(value, |_| true)
+            }
+        });
+
+        // the device lowers the flag before the callbacks registered after it run
+        let alive = self.1.tracker().flag(self.0);
+        let deleted = buffer.clone();
+        let on_deleting = subscription::on_parameter_deleting(&self.1, move |_| {
+            if !alive.load(Ordering::SeqCst) {
+                deleted.close()
+            }
+        });
+
+        ValueStream {
+            buffer,
+            _subscriptions: [on_value, on_deleting],
+        }
+    }
+
+    /// Asks the remote side for the current value without blocking the runtime.
+    ///
+    /// The request keeps running on the blocking pool when `timeout` expires first.
+    pub fn fetch_async(&self, timeout: Duration) -> impl Future<Output = Result<OwnedValue>> {
+        let param = self.clone();
+        async move {
+            let task =
+                tokio::task::spawn_blocking(move || OwnedValue::try_from(param.fetch_value()));
+
+            match tokio::time::timeout(timeout, task).await {
+                Ok(Ok(value)) => Ok(value?),
+                // the blocking task is only cancelled when the runtime shuts down
+                Ok(Err(e)) => match e.try_into_panic() {
+                    Ok(panic) => std::panic::resume_unwind(panic),
+                    Err(_) => Err(Error::Cancelled),
+                },
+                Err(_) => Err(Error::Timeout),
+            }
+        }
+    }
+}
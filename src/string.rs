@@ -0,0 +1,55 @@
+use crate::{ffi, Error, Result};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+};
+
+/// NUL-terminates a Rust string before it is handed to libossia.
+pub(crate) fn c_string(s: &str) -> Result<CString> {
+    Ok(CString::new(s)?)
+}
+
+/// Keeps the `CString`s alive while libossia reads the `const char**` array pointing at them.
+pub(crate) struct CStringArray {
+    _strings: Vec<CString>,
+    ptrs: Vec<*const c_char>,
+}
+
+impl CStringArray {
+    pub(crate) fn new(strings: &[&str]) -> Result<CStringArray> {
+        let strings = strings
+            .iter()
+            .map(|s| c_string(s))
+            .collect::<Result<Vec<_>>>()?;
+        let ptrs = strings.iter().map(|s| s.as_ptr()).collect();
+
+        Ok(CStringArray {
+            _strings: strings,
+            ptrs,
+        })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const *const c_char {
+        self.ptrs.as_ptr()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ptrs.len()
+    }
+}
+
+/// Copies a string allocated by libossia and releases it with `ossia_string_free`.
+pub(crate) unsafe fn take_string(ptr: *const c_char) -> Result<String> {
+    take_optional_string(ptr)?.ok_or(Error::NullHandle)
+}
+
+/// Same as [`take_string`], but a null pointer means the string is unset.
+pub(crate) unsafe fn take_optional_string(ptr: *const c_char) -> Result<Option<String>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+
+    let s = CStr::from_ptr(ptr).to_str().map(str::to_owned);
+    ffi::ossia_string_free(ptr as *mut c_char);
+    Ok(Some(s?))
+}
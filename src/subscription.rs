@@ -0,0 +1,249 @@
+use crate::{ffi, lock::lock, Device, Node, Parameter, Value};
+use std::{
+    cell::RefCell,
+    ffi::c_void,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+pub(crate) enum Registration {
+    /// The flag is lowered once the parameter is deleted, its callbacks are gone with it.
+    Value(
+        ffi::ossia_parameter_t,
+        ffi::ossia_value_callback_idx_t,
+        Arc<AtomicBool>,
+    ),
+    /// The flag is raised once the node is deleted, its callbacks are gone with it.
+    NodeDeleting(
+        ffi::ossia_node_t,
+        ffi::ossia_node_callback_idx_t,
+        Arc<AtomicBool>,
+    ),
+    NodeCreated(ffi::ossia_device_t, ffi::ossia_node_callback_idx_t),
+    NodeRemoving(ffi::ossia_device_t, ffi::ossia_node_callback_idx_t),
+    ParameterDeleting(ffi::ossia_device_t, ffi::ossia_parameter_callback_idx_t),
+}
+
+/// Keeps a callback registered with libossia alive and unregisters it when dropped.
+#[must_use = "the callback is unregistered as soon as the subscription is dropped"]
+pub struct Subscription {
+    registration: Registration,
+    ctx: *mut c_void,
+    free_ctx: unsafe fn(*mut c_void),
+    // unregistering goes through the device, it must outlive the subscription
+    _device: Device,
+}
+
+// the closure is `Send` and only ever reached through its mutex
+unsafe impl Send for Subscription {}
+
+impl Subscription {
+    /// Boxes `cb` and hands its address to `register`, which must register it with libossia.
+    pub(crate) fn new<F, R>(device: &Device, cb: F, register: R) -> Subscription
+    where
+        F: Send + 'static,
+        R: FnOnce(*mut c_void) -> Registration,
+    {
+        let ctx = Box::into_raw(Box::new(Mutex::new(cb))) as *mut c_void;
+        Subscription {
+            registration: register(ctx),
+            ctx,
+            free_ctx: free_ctx::<F>,
+            _device: device.clone(),
+        }
+    }
+
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsafe {
+            match self.registration {
+                Registration::Value(param, idx, ref alive) => {
+                    if alive.load(Ordering::SeqCst) {
+                        ffi::ossia_parameter_remove_callback(param, idx)
+                    }
+                }
+                Registration::NodeDeleting(node, idx, ref deleted) => {
+                    if !deleted.load(Ordering::SeqCst) {
+                        ffi::ossia_node_remove_deleting_callback(node, idx)
+                    }
+                }
+                Registration::NodeCreated(device, idx) => {
+                    ffi::ossia_device_remove_node_created_callback(device, idx)
+                }
+                Registration::NodeRemoving(device, idx) => {
+                    ffi::ossia_device_remove_node_removing_callback(device, idx)
+                }
+                Registration::ParameterDeleting(device, idx) => {
+                    ffi::ossia_device_remove_parameter_deleting_callback(device, idx)
+                }
+            }
+            // libossia no longer references the closure once it has been unregistered, unless it
+            // is the one dropping its own subscription, `call` frees it on its way out then
+            if !defer_free(self.ctx) {
+                (self.free_ctx)(self.ctx);
+            }
+        }
+    }
+}
+
+pub(crate) fn on_value<F>(param: &Parameter, cb: F) -> Subscription
+where
+    F: FnMut(Value) + Send + 'static,
+{
+    // the parameter can be deleted on its own or together with its node before the subscription
+    // is dropped, the device tracker notices both
+    let alive = param.1.tracker().flag(param.0);
+    let raw = param.0;
+    Subscription::new(&param.1, cb, |ctx| {
+        Registration::Value(
+            raw,
+            unsafe { ffi::ossia_parameter_add_callback(raw, Some(value_trampoline::<F>), ctx) },
+            alive,
+        )
+    })
+}
+
+pub(crate) fn on_node_deleting<F>(node: &Node, mut cb: F) -> Subscription
+where
+    F: FnMut(Node) + Send + 'static,
+{
+    let deleted = Arc::new(AtomicBool::new(false));
+    let flag = deleted.clone();
+    let device = node.1.clone();
+    let cb = move |raw| {
+        flag.store(true, Ordering::SeqCst);
+        cb(Node(raw, device.clone()))
+    };
+
+    let (raw, trampoline) = (node.0, trampoline(&cb));
+    Subscription::new(&node.1, cb, |ctx| {
+        Registration::NodeDeleting(
+            raw,
+            unsafe { ffi::ossia_node_add_deleting_callback(raw, Some(trampoline), ctx) },
+            deleted,
+        )
+    })
+}
+
+pub(crate) fn on_node_created<F>(device: &Device, mut cb: F) -> Subscription
+where
+    F: FnMut(Node) + Send + 'static,
+{
+    let owner = device.clone();
+    let cb = move |raw| cb(Node(raw, owner.clone()));
+
+    let (raw, trampoline) = (device.as_raw(), trampoline(&cb));
+    Subscription::new(device, cb, |ctx| {
+        Registration::NodeCreated(raw, unsafe {
+            ffi::ossia_device_add_node_created_callback(raw, Some(trampoline), ctx)
+        })
+    })
+}
+
+pub(crate) fn on_node_removing<F>(device: &Device, mut cb: F) -> Subscription
+where
+    F: FnMut(Node) + Send + 'static,
+{
+    let owner = device.clone();
+    let cb = move |raw| cb(Node(raw, owner.clone()));
+
+    let (raw, trampoline) = (device.as_raw(), trampoline(&cb));
+    Subscription::new(device, cb, |ctx| {
+        Registration::NodeRemoving(raw, unsafe {
+            ffi::ossia_device_add_node_removing_callback(raw, Some(trampoline), ctx)
+        })
+    })
+}
+
+pub(crate) fn on_parameter_deleting<F>(device: &Device, mut cb: F) -> Subscription
+where
+    F: FnMut(Parameter) + Send + 'static,
+{
+    let owner = device.clone();
+    let cb = move |raw| cb(Parameter(raw, owner.clone()));
+
+    let (raw, trampoline) = (device.as_raw(), trampoline(&cb));
+    Subscription::new(device, cb, |ctx| {
+        Registration::ParameterDeleting(raw, unsafe {
+            ffi::ossia_device_add_parameter_deleting_callback(raw, Some(trampoline), ctx)
+        })
+    })
+}
+
+unsafe fn free_ctx<F>(ctx: *mut c_void) {
+    drop(Box::from_raw(ctx as *mut Mutex<F>));
+}
+
+thread_local! {
+    /// The closures running on this thread, with whether their subscription was dropped
+    /// meanwhile.
+    static RUNNING: RefCell<Vec<(*mut c_void, bool)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks `ctx` to be freed once it returns if it is running on this thread.
+fn defer_free(ctx: *mut c_void) -> bool {
+    RUNNING.with(|running| {
+        match running
+            .borrow_mut()
+            .iter_mut()
+            .find(|(running, _)| *running == ctx)
+        {
+            Some((_, dropped)) => {
+                *dropped = true;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+unsafe fn call<F: FnMut(T), T>(ctx: *mut c_void, arg: T) {
+    // libossia calls back synchronously, so a closure pushing to its own parameter comes back
+    // here while it still holds its mutex, that nested call is skipped instead of deadlocking
+    let reentered = RUNNING.with(|running| {
+        let mut running = running.borrow_mut();
+        let reentered = running.iter().any(|(running, _)| *running == ctx);
+        if !reentered {
+            running.push((ctx, false));
+        }
+        reentered
+    });
+    if reentered {
+        return;
+    }
+
+    // unwinding into libossia would abort the process, the panic hook has already printed the
+    // message by the time the panic is caught here
+    let cb = &*(ctx as *const Mutex<F>);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut cb = lock(cb);
+        (*cb)(arg)
+    }));
+
+    let running = RUNNING.with(|running| running.borrow_mut().pop());
+    if let Some((_, true)) = running {
+        free_ctx::<F>(ctx);
+    }
+}
+
+pub(crate) unsafe extern "C" fn value_trampoline<F: FnMut(Value)>(
+    ctx: *mut c_void,
+    value: ffi::ossia_value_t,
+) {
+    // the callee owns the value libossia hands to the callback
+    call::<F, _>(ctx, Value(value))
+}
+
+/// Picks the trampoline for a closure taking a raw handle, whose type cannot be named.
+fn trampoline<F: FnMut(T), T>(_: &F) -> unsafe extern "C" fn(*mut c_void, T) {
+    unsafe extern "C" fn raw_trampoline<F: FnMut(T), T>(ctx: *mut c_void, raw: T) {
+        call::<F, _>(ctx, raw)
+    }
+    raw_trampoline::<F, T>
+}
@@ -1,91 +1,403 @@
 use crate::{ffi, Type};
 use std::{
-    convert::{TryFrom, TryInto},
+    convert::TryFrom,
     ffi::CStr,
-    os::raw::c_char,
+    fmt,
+    os::raw::{c_char, c_int},
 };
 
+#[repr(transparent)]
 pub struct Value(pub(crate) ffi::ossia_value_t);
 
-impl Into<i32> for Value {
-    fn into(self) -> i32 {
+/// Rust-native mirror of [`Value`] that can be inspected and pattern-matched without FFI calls.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedValue {
+    Float(f32),
+    Int(i32),
+    Vec2f(f32, f32),
+    Vec3f(f32, f32, f32),
+    Vec4f(f32, f32, f32, f32),
+    Impulse,
+    Bool(bool),
+    String(String),
+    List(Vec<OwnedValue>),
+    Char(c_char),
+    /// libossia stores byte arrays as strings, so only non-UTF-8 strings are decoded as `ByteArray`.
+    ByteArray(Vec<u8>),
+}
+
+impl Value {
+    pub fn get_type(&self) -> Result<Type, ConversionError> {
+        let raw = unsafe { ffi::ossia_value_get_type(self.0) as isize };
+        Type::try_from(raw).map_err(|_| ConversionError::UnknownType(raw))
+    }
+
+    pub(crate) fn bytes(&self) -> Vec<u8> {
+        let mut ptr: *mut c_char = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_value_to_byte_array(self.0, &mut ptr, &mut size) };
+
+        if ptr.is_null() {
+            return Vec::new();
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, size as usize) }.to_vec();
+        unsafe { ffi::ossia_string_free(ptr) };
+        bytes
+    }
+
+    pub(crate) fn elements(&self) -> Vec<Value> {
+        let mut ptr: *mut ffi::ossia_value_t = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_value_to_list(self.0, &mut ptr, &mut size) };
+        unsafe { take_list(ptr, size) }
+    }
+}
+
+impl TryFrom<&Value> for OwnedValue {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<OwnedValue, ConversionError> {
+        Ok(match value.get_type()? {
+            Type::Float => OwnedValue::Float(value.coerce()),
+            Type::Int => OwnedValue::Int(value.coerce()),
+            Type::Vec2f => {
+                let (a, b) = value.coerce();
+                OwnedValue::Vec2f(a, b)
+            }
+            Type::Vec3f => {
+                let (a, b, c) = value.coerce();
+                OwnedValue::Vec3f(a, b, c)
+            }
+            Type::Vec4f => {
+                let (a, b, c, d) = value.coerce();
+                OwnedValue::Vec4f(a, b, c, d)
+            }
+            Type::Impulse => OwnedValue::Impulse,
+            Type::Bool => OwnedValue::Bool(value.coerce()),
+            Type::String => match String::from_utf8(value.bytes()) {
+                Ok(s) => OwnedValue::String(s),
+                Err(e) => OwnedValue::ByteArray(e.into_bytes()),
+            },
+            Type::List => OwnedValue::List(
+                value
+                    .elements()
+                    .iter()
+                    .map(OwnedValue::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Type::Char => OwnedValue::Char(value.coerce()),
+        })
+    }
+}
+
+impl TryFrom<Value> for OwnedValue {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<OwnedValue, ConversionError> {
+        OwnedValue::try_from(&value)
+    }
+}
+
+impl From<&OwnedValue> for Value {
+    fn from(value: &OwnedValue) -> Value {
+        match value {
+            OwnedValue::Float(v) => Value::from(*v),
+            OwnedValue::Int(v) => Value::from(*v),
+            OwnedValue::Vec2f(a, b) => Value::from((*a, *b)),
+            OwnedValue::Vec3f(a, b, c) => Value::from((*a, *b, *c)),
+            OwnedValue::Vec4f(a, b, c, d) => Value::from((*a, *b, *c, *d)),
+            OwnedValue::Impulse => Value::from(()),
+            OwnedValue::Bool(v) => Value::from(*v),
+            OwnedValue::String(v) => Value::from(v.as_bytes()),
+            OwnedValue::List(v) => {
+                let elements: Vec<Value> = v.iter().map(Value::from).collect();
+                Value::from(elements.as_slice())
+            }
+            OwnedValue::Char(v) => Value::from(*v),
+            OwnedValue::ByteArray(v) => Value::from(v.as_slice()),
+        }
+    }
+}
+
+impl From<OwnedValue> for Value {
+    fn from(value: OwnedValue) -> Value {
+        Value::from(&value)
+    }
+}
+
+/// Explicit, possibly lossy conversion that lets libossia coerce the value to the requested type.
+pub trait Coerce<T> {
+    fn coerce(&self) -> T;
+}
+
+impl Coerce<i32> for Value {
+    fn coerce(&self) -> i32 {
         unsafe { ffi::ossia_value_to_int(self.0) }
     }
 }
 
-impl Into<f32> for Value {
-    fn into(self) -> f32 {
+impl Coerce<f32> for Value {
+    fn coerce(&self) -> f32 {
         unsafe { ffi::ossia_value_to_float(self.0) }
     }
 }
 
-impl Into<(f32, f32)> for Value {
-    fn into(self) -> (f32, f32) {
+impl Coerce<(f32, f32)> for Value {
+    fn coerce(&self) -> (f32, f32) {
         let o = unsafe { ffi::ossia_value_to_2f(self.0) };
         (o.val[0], o.val[1])
     }
 }
 
-impl Into<(f32, f32, f32)> for Value {
-    fn into(self) -> (f32, f32, f32) {
+impl Coerce<(f32, f32, f32)> for Value {
+    fn coerce(&self) -> (f32, f32, f32) {
         let o = unsafe { ffi::ossia_value_to_3f(self.0) };
         (o.val[0], o.val[1], o.val[2])
     }
 }
 
-impl Into<(f32, f32, f32, f32)> for Value {
-    fn into(self) -> (f32, f32, f32, f32) {
+impl Coerce<(f32, f32, f32, f32)> for Value {
+    fn coerce(&self) -> (f32, f32, f32, f32) {
         let o = unsafe { ffi::ossia_value_to_4f(self.0) };
         (o.val[0], o.val[1], o.val[2], o.val[3])
     }
 }
 
-impl Into<bool> for Value {
-    fn into(self) -> bool {
-        unsafe { ffi::ossia_value_to_bool(self.0) == 0 }
+impl Coerce<bool> for Value {
+    fn coerce(&self) -> bool {
+        unsafe { ffi::ossia_value_to_bool(self.0) != 0 }
     }
 }
 
-impl Into<c_char> for Value {
-    fn into(self) -> c_char {
+impl Coerce<c_char> for Value {
+    fn coerce(&self) -> c_char {
         unsafe { ffi::ossia_value_to_char(self.0) }
     }
 }
 
-impl Into<Vec<u8>> for Value {
-    fn into(self) -> Vec<u8> {
-        let mut ptr: *mut c_char = std::ptr::null_mut();
+impl Coerce<Vec<u8>> for Value {
+    fn coerce(&self) -> Vec<u8> {
+        self.bytes()
+    }
+}
+
+impl Coerce<String> for Value {
+    fn coerce(&self) -> String {
+        let ptr = unsafe { ffi::ossia_value_to_string(self.0) };
+        if ptr.is_null() {
+            return String::new();
+        }
+
+        let s = unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { ffi::ossia_string_free(ptr as *mut c_char) };
+        s
+    }
+}
+
+impl Coerce<Vec<Value>> for Value {
+    fn coerce(&self) -> Vec<Value> {
+        self.elements()
+    }
+}
+
+impl Coerce<Vec<f32>> for Value {
+    fn coerce(&self) -> Vec<f32> {
+        let mut ptr: *mut f32 = std::ptr::null_mut();
         let mut size: ffi::size_t = 0;
-        unsafe { ffi::ossia_value_to_byte_array(self.0, &mut ptr, &mut size) };
+        unsafe { ffi::ossia_value_to_fn(self.0, &mut ptr, &mut size) };
+        unsafe { copy_fn(ptr, size) }
+    }
+}
 
-        unsafe {
-            Vec::from_raw_parts(
-                ptr as *mut u8,
-                size.try_into().unwrap(),
-                size.try_into().unwrap(),
-            )
+impl Coerce<Vec<i32>> for Value {
+    fn coerce(&self) -> Vec<i32> {
+        let mut ptr: *mut c_int = std::ptr::null_mut();
+        let mut size: ffi::size_t = 0;
+        unsafe { ffi::ossia_value_to_in(self.0, &mut ptr, &mut size) };
+        unsafe { copy_in(ptr, size) }
+    }
+}
+
+/// Copies and frees an array returned by `ossia_value_to_fn` or `ossia_parameter_to_fn`.
+pub(crate) unsafe fn copy_fn(ptr: *mut f32, size: ffi::size_t) -> Vec<f32> {
+    if ptr.is_null() {
+        return Vec::new();
+    }
+
+    let values = std::slice::from_raw_parts(ptr, size as usize).to_vec();
+    ffi::ossia_value_free_fn(ptr);
+    values
+}
+
+/// Copies and frees an array returned by `ossia_value_to_in` or `ossia_parameter_to_in`.
+pub(crate) unsafe fn copy_in(ptr: *mut c_int, size: ffi::size_t) -> Vec<i32> {
+    if ptr.is_null() {
+        return Vec::new();
+    }
+
+    let values = std::slice::from_raw_parts(ptr, size as usize).to_vec();
+    ffi::ossia_value_free_in(ptr);
+    values
+}
+
+/// Takes ownership of the elements of an array returned by `ossia_value_to_list` or
+/// `ossia_parameter_to_list` and frees the array itself.
+pub(crate) unsafe fn take_list(ptr: *mut ffi::ossia_value_t, size: ffi::size_t) -> Vec<Value> {
+    if ptr.is_null() {
+        return Vec::new();
+    }
+
+    let values = std::slice::from_raw_parts(ptr, size as usize)
+        .iter()
+        .map(|&v| Value(v))
+        .collect();
+    ffi::ossia_value_free_list(ptr);
+    values
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    TypeMismatch { expected: Type, found: Type },
+    UnknownType(isize),
+    InvalidUtf8,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConversionError::TypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected a value of type {:?}, found {:?}",
+                    expected, found
+                )
+            }
+            ConversionError::UnknownType(raw) => write!(f, "unknown value type {}", raw),
+            ConversionError::InvalidUtf8 => write!(f, "string value is not valid UTF-8"),
         }
     }
 }
 
-impl Into<String> for Value {
-    fn into(self) -> String {
-        unsafe { CStr::from_ptr(ffi::ossia_value_to_string(self.0)) }
-            .to_str()
-            .unwrap()
-            .to_owned()
+impl std::error::Error for ConversionError {}
+
+impl Value {
+    fn expect(&self, expected: Type) -> Result<(), ConversionError> {
+        let found = self.get_type()?;
+        if found == expected {
+            Ok(())
+        } else {
+            Err(ConversionError::TypeMismatch { expected, found })
+        }
     }
 }
 
-impl Into<Vec<Value>> for Value {
-    fn into(self) -> Vec<Value> {
-        todo!()
+impl TryFrom<&Value> for () {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<(), ConversionError> {
+        value.expect(Type::Impulse)
     }
 }
 
-impl Into<Type> for Value {
-    fn into(self) -> Type {
-        Type::try_from(unsafe { ffi::ossia_value_get_type(self.0) as isize }).unwrap()
+impl TryFrom<&Value> for i32 {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<i32, ConversionError> {
+        value.expect(Type::Int).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for f32 {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<f32, ConversionError> {
+        value.expect(Type::Float).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for (f32, f32) {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<(f32, f32), ConversionError> {
+        value.expect(Type::Vec2f).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for (f32, f32, f32) {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<(f32, f32, f32), ConversionError> {
+        value.expect(Type::Vec3f).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for (f32, f32, f32, f32) {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<(f32, f32, f32, f32), ConversionError> {
+        value.expect(Type::Vec4f).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<bool, ConversionError> {
+        value.expect(Type::Bool).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for c_char {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<c_char, ConversionError> {
+        value.expect(Type::Char).map(|_| value.coerce())
+    }
+}
+
+impl TryFrom<&Value> for Vec<u8> {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<Vec<u8>, ConversionError> {
+        value.expect(Type::String).map(|_| value.bytes())
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<String, ConversionError> {
+        value.expect(Type::String)?;
+        String::from_utf8(value.bytes()).map_err(|_| ConversionError::InvalidUtf8)
+    }
+}
+
+impl TryFrom<&Value> for Vec<Value> {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<Vec<Value>, ConversionError> {
+        value.expect(Type::List).map(|_| value.elements())
+    }
+}
+
+impl TryFrom<&Value> for Vec<f32> {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<Vec<f32>, ConversionError> {
+        value.expect(Type::List)?;
+        value.elements().iter().map(f32::try_from).collect()
+    }
+}
+
+impl TryFrom<&Value> for Vec<i32> {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<Vec<i32>, ConversionError> {
+        value.expect(Type::List)?;
+        value.elements().iter().map(i32::try_from).collect()
     }
 }
 
@@ -139,7 +451,38 @@ impl From<c_char> for Value {
 
 impl From<&str> for Value {
     fn from(value: &str) -> Value {
-        Value(unsafe { ffi::ossia_value_create_string(value.as_ptr() as *const c_char) })
+        // passing the length explicitly avoids relying on a NUL terminator
+        Value::from(value.as_bytes())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::from(value.as_str())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Value {
+        Value::from(value.as_slice())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Value {
+        Value::from(value.as_slice())
+    }
+}
+
+impl From<Vec<i32>> for Value {
+    fn from(value: Vec<i32>) -> Value {
+        Value::from(value.as_slice())
+    }
+}
+
+impl From<Vec<f32>> for Value {
+    fn from(value: Vec<f32>) -> Value {
+        Value::from(value.as_slice())
     }
 }
 
@@ -0,0 +1,115 @@
+use crate::{ffi, Address, Device, Node};
+use std::collections::VecDeque;
+
+enum Order {
+    DepthFirst,
+    BreadthFirst,
+}
+
+/// Iterator over the nodes below a node, yielding each one with its address.
+pub struct Walk {
+    queue: VecDeque<(Address, ffi::ossia_node_t, usize)>,
+    last: Option<(Address, ffi::ossia_node_t, usize)>,
+    order: Order,
+    max_depth: Option<usize>,
+    skip_hidden: bool,
+    device: Device,
+}
+
+impl Walk {
+    fn new(address: Address, node: &Node, order: Order) -> Walk {
+        Walk {
+            queue: VecDeque::new(),
+            last: Some((address, node.0, 0)),
+            order,
+            max_depth: None,
+            skip_hidden: false,
+            device: node.1.clone(),
+        }
+    }
+
+    pub(crate) fn depth_first(address: Address, node: &Node) -> Walk {
+        Walk::new(address, node, Order::DepthFirst)
+    }
+
+    pub(crate) fn breadth_first(address: Address, node: &Node) -> Walk {
+        Walk::new(address, node, Order::BreadthFirst)
+    }
+
+    /// Stops descending below `depth`, direct children of the starting node are at depth 1.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Skips hidden nodes together with their whole subtree.
+    pub fn skip_hidden(mut self, skip_hidden: bool) -> Self {
+        self.skip_hidden = skip_hidden;
+        self
+    }
+
+    /// Prevents the children of the node returned last from being visited.
+    pub fn skip_subtree(&mut self) {
+        self.last = None;
+    }
+
+    fn expand(&mut self) {
+        let (address, node, depth) = match self.last.take() {
+            Some(last) => last,
+            None => return,
+        };
+        if matches!(self.max_depth, Some(max) if depth >= max) {
+            return;
+        }
+
+        // the children are listed in one go, so that they come from the same state of the tree
+        let _tree = self.device.lock_tree();
+        let node = Node(node, self.device.clone());
+        let skip_hidden = self.skip_hidden;
+        let children: Vec<_> = (0..node.num_children())
+            .filter_map(|i| node.child(i).ok())
+            .filter(|child| !(skip_hidden && child.hidden()))
+            .filter_map(|child| {
+                let name = child.name().ok()?;
+                Some((address.child(&name), child.0, depth + 1))
+            })
+            .collect();
+
+        match self.order {
+            Order::DepthFirst => {
+                for child in children.into_iter().rev() {
+                    self.queue.push_front(child);
+                }
+            }
+            Order::BreadthFirst => self.queue.extend(children),
+        }
+    }
+}
+
+impl Iterator for Walk {
+    type Item = (Address, Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.expand();
+        let (address, node, depth) = self.queue.pop_front()?;
+        self.last = Some((address.clone(), node, depth));
+        Some((address, Node(node, self.device.clone())))
+    }
+}
+
+/// Iterator over the parents of a node, from the closest one up to the root.
+pub struct Ancestors(std::vec::IntoIter<(Address, Node)>);
+
+impl Ancestors {
+    pub(crate) fn new(ancestors: Vec<(Address, Node)>) -> Ancestors {
+        Ancestors(ancestors.into_iter())
+    }
+}
+
+impl Iterator for Ancestors {
+    type Item = (Address, Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
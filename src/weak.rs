@@ -0,0 +1,210 @@
+use crate::{device::WeakDevice, ffi, index::Index, lock::lock, Address, Node, Parameter};
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+/// A `Node` that does not keep its device alive and notices when the node is removed.
+#[derive(Clone)]
+pub struct WeakNode {
+    node: ffi::ossia_node_t,
+    device: WeakDevice,
+    alive: Arc<AtomicBool>,
+}
+
+impl WeakNode {
+    /// Returns the node unless it or its device has been deleted since.
+    ///
+    /// The node can still be removed by another thread right after this returns.
+    pub fn upgrade(&self) -> Option<Node> {
+        let device = self.device.upgrade()?;
+        if self.alive.load(Ordering::SeqCst) {
+            Some(Node(self.node, device))
+        } else {
+            None
+        }
+    }
+}
+
+/// A `Parameter` that does not keep its device alive and notices when the parameter is deleted.
+#[derive(Clone)]
+pub struct WeakParameter {
+    param: ffi::ossia_parameter_t,
+    device: WeakDevice,
+    alive: Arc<AtomicBool>,
+}
+
+impl WeakParameter {
+    /// Returns the parameter unless it or its device has been deleted since.
+    ///
+    /// The parameter can still be deleted by another thread right after this returns.
+    pub fn upgrade(&self) -> Option<Parameter> {
+        let device = self.device.upgrade()?;
+        if self.alive.load(Ordering::SeqCst) {
+            Some(Parameter(self.param, device))
+        } else {
+            None
+        }
+    }
+}
+
+impl Node {
+    pub fn downgrade(&self) -> WeakNode {
+        WeakNode {
+            node: self.0,
+            device: self.1.downgrade(),
+            alive: self.1.tracker().flag(self.0),
+        }
+    }
+}
+
+impl Parameter {
+    pub fn downgrade(&self) -> WeakParameter {
+        WeakParameter {
+            param: self.0,
+            device: self.1.downgrade(),
+            alive: self.1.tracker().flag(self.0),
+        }
+    }
+}
+
+/// Follows the namespace of a device through its callbacks.
+///
+/// It keeps the liveness flags of the nodes and parameters that have been downgraded, and the
+/// `Index` of node addresses once one has been asked for.
+#[derive(Default)]
+pub(crate) struct Tracker(Mutex<State>);
+
+#[derive(Default)]
+struct State {
+    // entries stay until their node or parameter is deleted, so the map is bounded by the tree
+    alive: HashMap<*mut c_void, Arc<AtomicBool>>,
+    index: Index,
+}
+
+/// Indices of the device callbacks feeding a `Tracker`.
+pub(crate) struct Tracking {
+    node_created: ffi::ossia_node_callback_idx_t,
+    node_removing: ffi::ossia_node_callback_idx_t,
+    parameter_deleting: ffi::ossia_parameter_callback_idx_t,
+}
+
+impl Tracker {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.0)
+    }
+
+    /// The liveness flag of a node or parameter, lowered once libossia deletes it.
+    pub(crate) fn flag(&self, raw: *mut c_void) -> Arc<AtomicBool> {
+        self.lock()
+            .alive
+            .entry(raw)
+            .or_insert_with(|| Arc::new(AtomicBool::new(true)))
+            .clone()
+    }
+
+    /// The address of `node`, or of the removed node it was until another one is removed.
+    pub(crate) fn address(
+        &self,
+        device: ffi::ossia_device_t,
+        node: ffi::ossia_node_t,
+    ) -> Option<Address> {
+        self.lock().index.locate(device, node)
+    }
+
+    /// `node` followed by its ancestors up to the root, with their addresses.
+    pub(crate) fn lineage(
+        &self,
+        device: ffi::ossia_device_t,
+        node: ffi::ossia_node_t,
+    ) -> Option<Vec<(Address, ffi::ossia_node_t)>> {
+        self.lock().index.lineage(device, node)
+    }
+
+    /// Starts following the nodes created and deleted in `device`.
+    ///
+    /// `self` must not move nor be dropped before `stop` has been called.
+    pub(crate) unsafe fn start(&self, device: ffi::ossia_device_t) -> Tracking {
+        let ctx = self as *const Tracker as *mut c_void;
+        Tracking {
+            node_created: ffi::ossia_device_add_node_created_callback(
+                device,
+                Some(node_created),
+                ctx,
+            ),
+            node_removing: ffi::ossia_device_add_node_removing_callback(
+                device,
+                Some(node_removing),
+                ctx,
+            ),
+            parameter_deleting: ffi::ossia_device_add_parameter_deleting_callback(
+                device,
+                Some(parameter_deleting),
+                ctx,
+            ),
+        }
+    }
+
+    pub(crate) unsafe fn stop(&self, device: ffi::ossia_device_t, tracking: &Tracking) {
+        ffi::ossia_device_remove_node_created_callback(device, tracking.node_created);
+        ffi::ossia_device_remove_node_removing_callback(device, tracking.node_removing);
+        ffi::ossia_device_remove_parameter_deleting_callback(device, tracking.parameter_deleting);
+    }
+}
+
+impl State {
+    fn lower(&mut self, raw: *mut c_void) {
+        if let Some(flag) = self.alive.remove(&raw) {
+            flag.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// `node` and its descendants, each after its parent and paired with it.
+unsafe fn subtree(node: ffi::ossia_node_t) -> Vec<(ffi::ossia_node_t, ffi::ossia_node_t)> {
+    let mut nodes = vec![(node, ptr::null_mut())];
+    let mut i = 0;
+    while let Some(&(node, _)) = nodes.get(i) {
+        for j in 0..ffi::ossia_node_child_size(node) {
+            let child = ffi::ossia_node_get_child(node, j);
+            if !child.is_null() {
+                nodes.push((child, node));
+            }
+        }
+        i += 1;
+    }
+    nodes
+}
+
+unsafe extern "C" fn node_created(ctx: *mut c_void, node: ffi::ossia_node_t) {
+    let tracker = &*(ctx as *const Tracker);
+    tracker.lock().index.created(node);
+}
+
+unsafe extern "C" fn node_removing(ctx: *mut c_void, node: ffi::ossia_node_t) {
+    let tracker = &*(ctx as *const Tracker);
+    let mut state = tracker.lock();
+    if state.alive.is_empty() && !state.index.is_built() {
+        return;
+    }
+
+    // the children and parameters of the node go away with it
+    let removed = subtree(node);
+    for &(node, _) in &removed {
+        state.lower(node);
+        state.lower(ffi::ossia_node_get_parameter(node));
+    }
+    state
+        .index
+        .removed(ffi::ossia_node_get_device(node), &removed);
+}
+
+unsafe extern "C" fn parameter_deleting(ctx: *mut c_void, param: ffi::ossia_parameter_t) {
+    let tracker = &*(ctx as *const Tracker);
+    tracker.lock().lower(param);
+}
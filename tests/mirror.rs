@@ -0,0 +1,72 @@
+//! Mirrors an OSCQuery server living in the same process, then follows it going away and coming
+//! back.
+
+use ossia::{
+    Address, ConnectionState, Device, MirrorConnection, OscQueryMirrorConfig, OscQueryServerConfig,
+    ReconnectPolicy,
+};
+use std::{convert::TryFrom, time::Duration};
+
+const OSC_PORT: u16 = 17234;
+const WS_PORT: u16 = 17235;
+
+fn serve() -> Device {
+    let protocol = OscQueryServerConfig::new()
+        .osc_port(OSC_PORT)
+        .ws_port(WS_PORT)
+        .build()
+        .unwrap();
+    let device = Device::new(protocol, "server").unwrap();
+    device
+        .root()
+        .unwrap()
+        .parameter_builder::<f32>("gain")
+        .default(0.5)
+        .build()
+        .unwrap();
+    device
+}
+
+/// Skips the events of `mirror` up to the first one matching `wanted`.
+fn wait_for(mirror: &MirrorConnection, wanted: fn(&ConnectionState) -> bool) {
+    loop {
+        let state = mirror
+            .events()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        if wanted(&state) {
+            return;
+        }
+    }
+}
+
+fn gain(mirror: &MirrorConnection) -> f32 {
+    let node = mirror
+        .device()
+        .find(Address::new("/gain").unwrap())
+        .unwrap();
+    let param = node.parameter().unwrap();
+    f32::try_from(&param.fetch_value()).unwrap()
+}
+
+#[test]
+fn reconnects_to_a_restarted_server() {
+    let server = serve();
+
+    let config = OscQueryMirrorConfig::new(([127, 0, 0, 1], WS_PORT).into());
+    let policy = ReconnectPolicy::new()
+        .initial_delay(Duration::from_millis(50))
+        .probe_interval(Duration::from_millis(100));
+    let mirror = MirrorConnection::connect(&config, "mirror", policy).unwrap();
+    assert_eq!(mirror.state(), ConnectionState::Connected);
+    assert_eq!(gain(&mirror), 0.5);
+
+    drop(server);
+    wait_for(&mirror, |state| {
+        matches!(state, ConnectionState::Disconnected { .. })
+    });
+
+    let _server = serve();
+    wait_for(&mirror, |state| *state == ConnectionState::Connected);
+    assert_eq!(gain(&mirror), 0.5);
+}